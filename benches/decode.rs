@@ -0,0 +1,30 @@
+use cat_finder::{DetectorConfig, decode_predictions};
+use criterion::{Criterion, criterion_group, criterion_main};
+use ndarray::{Array, ArrayD, IxDyn};
+use std::hint::black_box;
+
+/// A stock YOLOv8 head, `[1, 84, 8400]`: boxes spread over the 640x640
+/// input and class scores mostly low, with a few anchors over the default
+/// threshold as on a real photo
+fn synthetic_head() -> ArrayD<f32> {
+    Array::from_shape_fn(IxDyn(&[1, 84, 8400]), |index| {
+        let (row, anchor) = (index[1], index[2]);
+        match row {
+            0 | 1 => (anchor % 640) as f32,
+            2 | 3 => 32.0 + (anchor % 97) as f32,
+            _ => ((row * 7919 + anchor * 104_729) % 1000) as f32 / 3000.0,
+        }
+    })
+}
+
+fn decode(c: &mut Criterion) {
+    let head = synthetic_head();
+    let config = DetectorConfig::default();
+
+    c.bench_function("decode_predictions [1, 84, 8400]", |b| {
+        b.iter(|| decode_predictions(black_box(&head.view()), (1.0, 1.0), &config, config.confidence))
+    });
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -63,4 +63,17 @@ echo -e "${GREEN}Testing without verbose (just results):${NC}"
 # Test with custom confidence threshold
 echo ""
 echo -e "${GREEN}Testing with higher confidence (0.5):${NC}"
-"${PROJECT_DIR}/target/release/cat-finder" "$TEST_DIR" --confidence 0.5 --verbose
\ No newline at end of file
+"${PROJECT_DIR}/target/release/cat-finder" "$TEST_DIR" --confidence 0.5 --verbose
+
+# Same model, inputs and thresholds must give byte-for-byte identical output
+echo ""
+echo -e "${GREEN}Testing reproducibility (two identical runs):${NC}"
+FIRST_RUN=$("${PROJECT_DIR}/target/release/cat-finder" "$TEST_DIR" --sort name --format json 2>/dev/null)
+SECOND_RUN=$("${PROJECT_DIR}/target/release/cat-finder" "$TEST_DIR" --sort name --format json 2>/dev/null)
+if [ "$FIRST_RUN" == "$SECOND_RUN" ]; then
+    echo -e "${GREEN}PASS: both runs produced identical output${NC}"
+else
+    echo -e "${RED}FAIL: runs differ${NC}"
+    diff <(echo "$FIRST_RUN") <(echo "$SECOND_RUN")
+    exit 1
+fi
@@ -1,20 +1,51 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use std::collections::HashMap;
-use std::fs;
-use std::io::Read;
+use cat_finder::{
+    calculate_sha256_with_buffer, contents_equal, csv_field, dhash, hamming_distance, is_image_file, open_image,
+};
+use clap::{Parser, ValueEnum};
+use image::DynamicImage;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+/// Default most differing bits between [`dhash`]es for a --perceptual match
+const DEFAULT_PERCEPTUAL_DISTANCE: u32 = 5;
+
 #[derive(Parser, Debug)]
 #[command(name = "find-duplicates")]
 #[command(about = "Find duplicate images by comparing file size and SHA-256 checksum")]
 struct Args {
     /// Path to the target image to find duplicates of
-    target: PathBuf,
+    #[arg(required_unless_present_any = ["all", "verify"])]
+    target: Option<PathBuf>,
 
     /// Directory to search for duplicates
-    search_dir: PathBuf,
+    #[arg(required_unless_present_any = ["all", "verify"])]
+    search_dir: Option<PathBuf>,
+
+    /// Instead of one target, list every group of identical images under DIR
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["target", "search_dir", "keep", "byte_compare"]
+    )]
+    all: Option<PathBuf>,
+
+    /// With --all, also write group_id,path,size,sha256 rows to this CSV file
+    #[arg(long, value_name = "FILE", requires = "all")]
+    csv: Option<PathBuf>,
+
+    /// Instead of searching, re-hash the files of a path,sha256 manifest and report
+    /// the corrupt (changed) and missing ones
+    #[arg(
+        long,
+        value_name = "MANIFEST",
+        conflicts_with_all = ["target", "search_dir", "all", "keep", "byte_compare", "perceptual"]
+    )]
+    verify: Option<PathBuf>,
 
     /// Show verbose output
     #[arg(short, long)]
@@ -23,29 +54,102 @@ struct Args {
     /// Show checksums in output
     #[arg(short = 'c', long)]
     show_checksums: bool,
-}
 
-fn calculate_sha256(path: &Path) -> Result<String> {
-    use sha2::{Digest, Sha256};
+    /// Read size in KiB when hashing files, up to 1048576
+    #[arg(long, default_value = "256", value_name = "KB", value_parser = clap::value_parser!(u32).range(1..=1_048_576))]
+    hash_buffer_kb: u32,
+
+    /// Compare candidates byte by byte with the target, stopping at the first
+    /// difference, instead of hashing each one
+    #[arg(long)]
+    byte_compare: bool,
+
+    /// Keep one file of the target and its duplicates and print the others as delete candidates
+    #[arg(long, value_enum, value_name = "POLICY")]
+    keep: Option<KeepPolicy>,
+
+    /// Delete the candidates chosen by --keep
+    #[arg(long, requires = "keep")]
+    delete: bool,
 
-    let mut file = fs::File::open(path)
-        .with_context(|| format!("Failed to open file: {}", path.display()))?;
+    /// With --delete, print what would be deleted without deleting
+    #[arg(long, requires = "delete")]
+    dry_run: bool,
 
-    let mut hasher = Sha256::new();
-    let mut buffer = [0; 8192];
+    /// Find images that look like the target (re-encoded, resized) by perceptual hash
+    /// instead of identical files
+    #[arg(long, conflicts_with_all = ["all", "byte_compare", "keep", "show_checksums"])]
+    perceptual: bool,
+
+    /// Most differing hash bits (of 64) for a --perceptual match
+    #[arg(long, value_name = "BITS", default_value_t = DEFAULT_PERCEPTUAL_DISTANCE, requires = "perceptual")]
+    max_distance: u32,
+
+    /// With --perceptual, also match copies that were rotated or mirrored
+    #[arg(long, requires = "perceptual")]
+    rotation_invariant: bool,
+}
+
+/// Which copy of a duplicate group --keep keeps
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum KeepPolicy {
+    /// Earliest modification time (usually the original)
+    Oldest,
+    /// Latest modification time
+    Newest,
+    /// The target file
+    First,
+    /// Longest path, e.g. the copy filed deepest in an organized tree
+    LargestPath,
+}
 
-    loop {
-        let bytes_read = file.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
+impl KeepPolicy {
+    /// Index into `files` of the file to keep; ties go to the earlier file
+    fn choose(self, files: &[PathBuf]) -> usize {
+        let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+
+        match self {
+            KeepPolicy::First => 0,
+            // Files without a readable time never win
+            KeepPolicy::Oldest => position_max(files, |path| modified(path).map(Reverse)),
+            KeepPolicy::Newest => position_max(files, modified),
+            KeepPolicy::LargestPath => position_max(files, |path| path.as_os_str().len()),
         }
-        hasher.update(&buffer[..bytes_read]);
+    }
+}
+
+/// Index of the first file with the greatest `key`
+fn position_max<K: Ord>(files: &[PathBuf], key: impl Fn(&Path) -> K) -> usize {
+    files
+        .iter()
+        .enumerate()
+        .max_by_key(|&(index, path)| (key(path), Reverse(index)))
+        .map_or(0, |(index, _)| index)
+}
+
+/// Byte count in binary units, e.g. `512 bytes`, `3.4 MB`
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+
+    if bytes < 1024 {
+        return format!("{bytes} bytes");
     }
 
-    Ok(format!("{:x}", hasher.finalize()))
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
 }
 
-fn get_file_info(path: &Path) -> Result<(u64, String, String)> {
+/// The canonical path of `path`, naming its file however it was reached
+fn canonical(path: &Path) -> Result<PathBuf> {
+    fs::canonicalize(path).with_context(|| format!("Failed to resolve {}", path.display()))
+}
+
+fn get_file_info(path: &Path, hash_buffer_size: usize) -> Result<(u64, String, String)> {
     let metadata = fs::metadata(path)
         .with_context(|| format!("Failed to get metadata for: {}", path.display()))?;
 
@@ -55,48 +159,365 @@ fn get_file_info(path: &Path) -> Result<(u64, String, String)> {
         .unwrap_or("")
         .to_lowercase();
 
-    let checksum = calculate_sha256(path)?;
+    let checksum = calculate_sha256_with_buffer(path, hash_buffer_size)?;
 
     Ok((size, extension, checksum))
 }
 
+/// [`dhash`] of `img` in all 8 orientations: each quarter turn, plain and mirrored
+fn orientation_hashes(img: &DynamicImage) -> Vec<u64> {
+    [img.clone(), img.rotate90(), img.rotate180(), img.rotate270()]
+        .iter()
+        .flat_map(|turned| [dhash(turned), dhash(&turned.fliph())])
+        .collect()
+}
+
+/// --perceptual: print the images under `search_dir` whose hash is within
+/// --max-distance of the target's
+fn run_perceptual(target: &Path, search_dir: &Path, args: &Args) -> Result<()> {
+    let img = open_image(target, None).with_context(|| format!("Failed to decode target: {}", target.display()))?;
+    // Turning the target instead of every candidate gives the same matches for 8 hashes in total
+    let target_hashes = if args.rotation_invariant {
+        orientation_hashes(&img)
+    } else {
+        vec![dhash(&img)]
+    };
+
+    if args.verbose {
+        eprintln!("Target file: {}", target.display());
+        eprintln!("  Perceptual hash: {:016x}", target_hashes[0]);
+        eprintln!();
+        eprintln!("Searching in: {}", search_dir.display());
+        eprintln!();
+    }
+
+    let target_file = canonical(target)?;
+    let mut checked_count = 0;
+    let mut found_count = 0;
+    let mut error_count = 0;
+
+    for entry in WalkDir::new(search_dir).follow_links(true).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_file() || !is_image_file(path) || fs::canonicalize(path).is_ok_and(|file| file == target_file) {
+            continue;
+        }
+
+        checked_count += 1;
+        if args.verbose {
+            eprint!("Checking: {} ... ", path.display());
+        }
+
+        let hash = match open_image(path, None) {
+            Ok(img) => dhash(&img),
+            Err(e) => {
+                error_count += 1;
+                if args.verbose {
+                    eprintln!("ERROR: {}", e);
+                }
+                continue;
+            }
+        };
+
+        let distance = target_hashes
+            .iter()
+            .map(|&target_hash| hamming_distance(target_hash, hash))
+            .min()
+            .unwrap_or(u32::MAX);
+        if distance <= args.max_distance {
+            found_count += 1;
+            if args.verbose {
+                eprintln!("MATCH! (distance {distance})");
+            }
+            println!("{}", path.display());
+        } else if args.verbose {
+            eprintln!("different (distance {distance})");
+        }
+    }
+
+    if args.verbose {
+        eprintln!();
+        eprintln!("Summary:");
+        eprintln!("  Images hashed: {}", checked_count);
+        eprintln!("  Similar images found: {}", found_count);
+        eprintln!("  Errors: {}", error_count);
+    }
+
+    Ok(())
+}
+
+/// Files under one directory with identical contents
+struct DuplicateGroup {
+    size: u64,
+    sha256: String,
+    /// Sorted, at least two
+    paths: Vec<PathBuf>,
+}
+
+/// Every group of identical image files under `dir`, ordered by first path.
+/// Only files sharing a size with another file are hashed.
+fn find_groups(dir: &Path, hash_buffer_size: usize, verbose: bool) -> Vec<DuplicateGroup> {
+    let mut files_by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for entry in WalkDir::new(dir).follow_links(true).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.is_file() || !is_image_file(path) {
+            continue;
+        }
+        if let Ok(metadata) = fs::metadata(path) {
+            files_by_size.entry(metadata.len()).or_default().push(path.to_path_buf());
+        }
+    }
+
+    let mut groups = Vec::new();
+    for (size, paths) in files_by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        let mut by_hash: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for path in paths {
+            match calculate_sha256_with_buffer(&path, hash_buffer_size) {
+                Ok(sha256) => by_hash.entry(sha256).or_default().push(path),
+                Err(e) => {
+                    if verbose {
+                        eprintln!("ERROR hashing {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+
+        for (sha256, mut paths) in by_hash.into_iter().filter(|(_, paths)| paths.len() > 1) {
+            paths.sort();
+            groups.push(DuplicateGroup { size, sha256, paths });
+        }
+    }
+
+    // Sorting makes group IDs stable across runs over the same tree
+    groups.sort_by(|a, b| a.paths[0].cmp(&b.paths[0]));
+    groups
+}
+
+fn write_groups_csv(path: &Path, groups: &[DuplicateGroup]) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create CSV file: {}", path.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    writeln!(writer, "group_id,path,size,sha256")?;
+    for (index, group) in groups.iter().enumerate() {
+        for file in &group.paths {
+            let file = csv_field(&file.display().to_string());
+            writeln!(writer, "{},{file},{},{}", index + 1, group.size, group.sha256)?;
+        }
+    }
+
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write CSV file: {}", path.display()))
+}
+
+/// --all: print every duplicate group under `dir`
+fn run_all(dir: &Path, args: &Args) -> Result<()> {
+    if !dir.is_dir() {
+        anyhow::bail!("Search path is not a directory: {}", dir.display());
+    }
+
+    let groups = find_groups(dir, args.hash_buffer_kb as usize * 1024, args.verbose);
+
+    for (index, group) in groups.iter().enumerate() {
+        println!("Group {} ({} files, {} each):", index + 1, group.paths.len(), format_bytes(group.size));
+        for path in &group.paths {
+            if args.show_checksums {
+                println!("  {} [SHA-256: {}]", path.display(), group.sha256);
+            } else {
+                println!("  {}", path.display());
+            }
+        }
+    }
+
+    if let Some(csv) = &args.csv {
+        write_groups_csv(csv, &groups)?;
+    }
+
+    if args.verbose {
+        let redundant: u64 = groups
+            .iter()
+            .map(|group| group.size * (group.paths.len() as u64 - 1))
+            .sum();
+        eprintln!();
+        eprintln!("Summary:");
+        eprintln!("  Duplicate groups: {}", groups.len());
+        eprintln!("  Files in groups: {}", groups.iter().map(|group| group.paths.len()).sum::<usize>());
+        eprintln!("  Space used by redundant copies: {}", format_bytes(redundant));
+    }
+
+    Ok(())
+}
+
+/// One `path,sha256` manifest entry, the path resolved against the manifest's directory
+struct ManifestEntry {
+    path: PathBuf,
+    sha256: String,
+}
+
+/// Read a `path,sha256` manifest. The header row is optional, blank lines are
+/// skipped, and paths may be CSV-quoted or contain commas of their own.
+fn read_hash_manifest(manifest: &Path) -> Result<Vec<ManifestEntry>> {
+    let contents =
+        fs::read_to_string(manifest).with_context(|| format!("Failed to read manifest: {}", manifest.display()))?;
+    let base = manifest.parent().unwrap_or(Path::new(""));
+    let is_sha256 = |value: &str| value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit());
+
+    let mut entries = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let Some((path, sha256)) = line.rsplit_once(',') else {
+            anyhow::bail!("{}: line {}: expected path,sha256", manifest.display(), index + 1);
+        };
+        let sha256 = sha256.trim();
+        if !is_sha256(sha256) {
+            if index == 0 {
+                continue;
+            }
+            anyhow::bail!("{}: line {}: '{sha256}' is not a SHA-256 checksum", manifest.display(), index + 1);
+        }
+
+        let path = path.trim();
+        let path = match path.strip_prefix('"').and_then(|path| path.strip_suffix('"')) {
+            Some(quoted) => quoted.replace("\"\"", "\""),
+            None => path.to_string(),
+        };
+        entries.push(ManifestEntry {
+            path: base.join(path),
+            sha256: sha256.to_lowercase(),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// --verify: re-hash every file of `manifest` and print the ones whose
+/// contents changed (`CORRUPT`) or that are gone (`MISSING`)
+fn run_verify(manifest: &Path, args: &Args) -> Result<()> {
+    let entries = read_hash_manifest(manifest)?;
+
+    let mut corrupt_count = 0;
+    let mut missing_count = 0;
+    let mut error_count = 0;
+
+    for entry in &entries {
+        if args.verbose {
+            eprint!("Verifying: {} ... ", entry.path.display());
+        }
+
+        if !entry.path.exists() {
+            missing_count += 1;
+            if args.verbose {
+                eprintln!("MISSING");
+            }
+            println!("MISSING {}", entry.path.display());
+            continue;
+        }
+
+        match calculate_sha256_with_buffer(&entry.path, args.hash_buffer_kb as usize * 1024) {
+            Ok(sha256) if sha256 == entry.sha256 => {
+                if args.verbose {
+                    eprintln!("ok");
+                }
+            }
+            Ok(sha256) => {
+                corrupt_count += 1;
+                if args.verbose {
+                    eprintln!("CORRUPT");
+                }
+                if args.show_checksums {
+                    println!("CORRUPT {} [expected: {}, SHA-256: {sha256}]", entry.path.display(), entry.sha256);
+                } else {
+                    println!("CORRUPT {}", entry.path.display());
+                }
+            }
+            Err(e) => {
+                error_count += 1;
+                if args.verbose {
+                    eprintln!("ERROR: {}", e);
+                } else {
+                    eprintln!("ERROR reading {}: {}", entry.path.display(), e);
+                }
+            }
+        }
+    }
+
+    if args.verbose {
+        eprintln!();
+        eprintln!("Summary:");
+        eprintln!("  Files in manifest: {}", entries.len());
+        eprintln!("  Verified: {}", entries.len() - corrupt_count - missing_count - error_count);
+        eprintln!("  Corrupt: {}", corrupt_count);
+        eprintln!("  Missing: {}", missing_count);
+        eprintln!("  Errors: {}", error_count);
+    }
+
+    let failed = corrupt_count + missing_count + error_count;
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} files failed verification", entries.len());
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    if let Some(manifest) = &args.verify {
+        return run_verify(manifest, &args);
+    }
+
+    if let Some(dir) = &args.all {
+        return run_all(dir, &args);
+    }
+    let (Some(target), Some(search_dir)) = (&args.target, &args.search_dir) else {
+        unreachable!("clap requires both paths without --all or --verify");
+    };
+
     // Get target file info
-    if !args.target.exists() {
-        anyhow::bail!("Target file does not exist: {}", args.target.display());
+    if !target.exists() {
+        anyhow::bail!("Target file does not exist: {}", target.display());
     }
 
-    if !args.target.is_file() {
-        anyhow::bail!("Target path is not a file: {}", args.target.display());
+    if !target.is_file() {
+        anyhow::bail!("Target path is not a file: {}", target.display());
     }
 
-    let (target_size, target_ext, target_checksum) = get_file_info(&args.target)?;
+    if args.perceptual {
+        return run_perceptual(target, search_dir, &args);
+    }
+
+    let (target_size, target_ext, target_checksum) = get_file_info(target, args.hash_buffer_kb as usize * 1024)?;
+    let target_file = canonical(target)?;
 
     if args.verbose {
-        eprintln!("Target file: {}", args.target.display());
+        eprintln!("Target file: {}", target.display());
         eprintln!("  Size: {} bytes", target_size);
         eprintln!("  Extension: .{}", target_ext);
         eprintln!("  SHA-256: {}", target_checksum);
         eprintln!();
-        eprintln!("Searching in: {}", args.search_dir.display());
+        eprintln!("Searching in: {}", search_dir.display());
         eprintln!();
     }
 
     let mut found_count = 0;
     let mut checked_count = 0;
     let mut size_matches = 0;
+    // With --keep, duplicates are printed once the whole group is known
+    let mut duplicates: Vec<PathBuf> = Vec::new();
 
     // Track files by size for efficiency
     let mut files_by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    // Every file is listed once, however many paths (symlinks) lead to it
+    let mut seen_files = HashSet::from([target_file.clone()]);
 
     // First pass: collect files by size
     if args.verbose {
         eprintln!("Phase 1: Scanning directory for files...");
     }
 
-    for entry in WalkDir::new(&args.search_dir)
+    for entry in WalkDir::new(search_dir)
         .follow_links(true)
         .into_iter()
         .filter_map(|e| e.ok())
@@ -107,9 +528,11 @@ fn main() -> Result<()> {
             continue;
         }
 
-        // Skip the target file itself if it's in the search directory
-        if path == args.target {
-            continue;
+        // Skip the target file itself if it's in the search directory, under
+        // any path, and further paths to a file already listed
+        match fs::canonicalize(path) {
+            Ok(file) if seen_files.insert(file) => {}
+            _ => continue,
         }
 
         // Check extension matches
@@ -131,7 +554,11 @@ fn main() -> Result<()> {
 
     // Second pass: check checksums only for files with matching size
     if args.verbose {
-        eprintln!("Phase 2: Checking checksums for size matches...");
+        if args.byte_compare {
+            eprintln!("Phase 2: Comparing size matches byte by byte...");
+        } else {
+            eprintln!("Phase 2: Checking checksums for size matches...");
+        }
         eprintln!();
     }
 
@@ -145,22 +572,31 @@ fn main() -> Result<()> {
                 eprint!("Checking: {} ... ", path.display());
             }
 
-            match calculate_sha256(path) {
-                Ok(checksum) => {
-                    if checksum == target_checksum {
+            let buffer_size = args.hash_buffer_kb as usize * 1024;
+            let is_duplicate = if args.byte_compare {
+                contents_equal(target, path, buffer_size)
+            } else {
+                calculate_sha256_with_buffer(path, buffer_size).map(|checksum| checksum == target_checksum)
+            };
+
+            match is_duplicate {
+                Ok(is_duplicate) => {
+                    if is_duplicate {
                         found_count += 1;
 
                         if args.verbose {
                             eprintln!("MATCH!");
                         }
 
-                        if args.show_checksums {
-                            println!("{} [SHA-256: {}]", path.display(), checksum);
+                        if args.keep.is_some() {
+                            duplicates.push(path.clone());
+                        } else if args.show_checksums {
+                            println!("{} [SHA-256: {}]", path.display(), target_checksum);
                         } else {
                             println!("{}", path.display());
                         }
                     } else if args.verbose {
-                        eprintln!("different checksum");
+                        eprintln!("different {}", if args.byte_compare { "contents" } else { "checksum" });
                     }
                 }
                 Err(e) => {
@@ -172,12 +608,66 @@ fn main() -> Result<()> {
         }
     }
 
+    let mut deleted_count = 0;
+    if let Some(policy) = args.keep
+        && !duplicates.is_empty()
+    {
+        let mut group = vec![target.clone()];
+        group.append(&mut duplicates);
+        let keep = group.remove(policy.choose(&group));
+        eprintln!("Keeping: {}", keep.display());
+
+        let keep_file = canonical(&keep)?;
+
+        // Everything else in the group is a delete candidate
+        for path in &group {
+            // Never delete the kept copy through another path to it
+            match fs::canonicalize(path) {
+                Ok(file) if file != keep_file => {}
+                Ok(_) => {
+                    eprintln!("Not deleting {}: it is the kept file", path.display());
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("ERROR resolving {}: {}", path.display(), e);
+                    continue;
+                }
+            }
+
+            if args.show_checksums {
+                println!("{} [SHA-256: {}]", path.display(), target_checksum);
+            } else {
+                println!("{}", path.display());
+            }
+
+            if args.dry_run {
+                eprintln!("Would delete: {}", path.display());
+            } else if args.delete {
+                match fs::remove_file(path) {
+                    Ok(()) => deleted_count += 1,
+                    Err(e) => eprintln!("ERROR deleting {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+
     if args.verbose {
         eprintln!();
         eprintln!("Summary:");
         eprintln!("  Files with matching size: {}", size_matches);
-        eprintln!("  Checksums calculated: {}", checked_count);
+        if args.byte_compare {
+            eprintln!("  Files compared: {}", checked_count);
+        } else {
+            eprintln!("  Checksums calculated: {}", checked_count);
+        }
         eprintln!("  Duplicates found: {}", found_count);
+        // Every copy beyond the one kept is redundant
+        let redundant = target_size * found_count as u64;
+        eprintln!("  Space used by redundant copies: {}", format_bytes(redundant));
+        if args.delete && !args.dry_run {
+            eprintln!("  Files deleted: {}", deleted_count);
+            eprintln!("  Space freed: {}", format_bytes(target_size * deleted_count as u64));
+        }
     }
 
     Ok(())
@@ -0,0 +1,80 @@
+use crate::crop::crop_rect;
+use crate::error::{DetectError, Result};
+use crate::hash::{dhash, hamming_distance};
+use crate::scan::ScanResult;
+use std::path::PathBuf;
+
+/// Default largest [`hamming_distance`] between crops of the same burst
+pub const DEFAULT_BURST_DISTANCE: u32 = 6;
+
+/// Groups matched photos that likely show the same cat in the same pose.
+///
+/// Each image is reduced to a perceptual hash ([`dhash`]) of its most
+/// confident detection's crop, so burst shots match even when the rest of
+/// the frame shifts. Photos whose crop hashes are within `max_distance` bits
+/// of each other, directly or through a chain of such photos, form a group.
+pub struct BurstFinder {
+    max_distance: u32,
+    hashes: Vec<(PathBuf, u64)>,
+}
+
+impl BurstFinder {
+    pub fn new(max_distance: u32) -> Self {
+        Self {
+            max_distance,
+            hashes: Vec::new(),
+        }
+    }
+
+    /// Hash the best detection of `result`; images without detections are ignored
+    pub fn add(&mut self, result: &ScanResult) -> Result<()> {
+        let Some(best) = result.detections.iter().max_by(|a, b| a.confidence.total_cmp(&b.confidence)) else {
+            return Ok(());
+        };
+
+        let img = image::open(&result.path).map_err(|e| DetectError::image(&result.path, e))?;
+        let Some((x, y, width, height)) = crop_rect(&best.bbox, img.width(), img.height()) else {
+            return Ok(());
+        };
+
+        let hash = dhash(&img.crop_imm(x, y, width, height));
+        self.hashes.push((result.path.clone(), hash));
+        Ok(())
+    }
+
+    /// Groups of two or more photos, each in the order they were added
+    pub fn groups(&self) -> Vec<Vec<PathBuf>> {
+        // Union-find over every pair close enough to be the same shot
+        let mut parent: Vec<usize> = (0..self.hashes.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        for i in 0..self.hashes.len() {
+            for j in i + 1..self.hashes.len() {
+                if hamming_distance(self.hashes[i].1, self.hashes[j].1) <= self.max_distance {
+                    let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+        }
+
+        let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+        let mut group_of_root = vec![None; self.hashes.len()];
+        for (i, (path, _)) in self.hashes.iter().enumerate() {
+            let r = root(&mut parent, i);
+            let index = *group_of_root[r].get_or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[index].push(path.clone());
+        }
+
+        groups.retain(|group| group.len() > 1);
+        groups
+    }
+}
@@ -0,0 +1,59 @@
+use crate::error::{DetectError, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Maps raw model scores to calibrated probabilities.
+///
+/// Both methods work on the score's logit `z = ln(p / (1 - p))` and return
+/// `sigmoid(a * z + b)`: temperature scaling is `a = 1 / temperature` with
+/// `b = 0`, Platt scaling fits `a` and `b` freely. `a` must be positive, so a
+/// higher score always stays higher and the best class per box is unchanged.
+///
+/// Loaded from a TOML file such as
+///
+/// ```toml
+/// method = "platt"
+/// a = 0.8
+/// b = -0.4
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(tag = "method", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Calibration {
+    Temperature { temperature: f32 },
+    Platt { a: f32, b: f32 },
+}
+
+impl Calibration {
+    pub fn load(path: &Path) -> Result<Self> {
+        let invalid = |message: String| DetectError::InvalidInput {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let text = fs::read_to_string(path).map_err(DetectError::io("failed to read calibration", path))?;
+        let calibration: Self = toml::from_str(&text).map_err(|e| invalid(e.message().to_string()))?;
+
+        let (a, b) = calibration.coefficients();
+        if !(a.is_finite() && a > 0.0 && b.is_finite()) {
+            return Err(invalid("temperature and a must be positive and finite".to_string()));
+        }
+
+        Ok(calibration)
+    }
+
+    /// Calibrated probability for a raw score in 0-1
+    pub fn apply(&self, score: f32) -> f32 {
+        let (a, b) = self.coefficients();
+        let p = score.clamp(1e-7, 1.0 - 1e-7);
+        let logit = (p / (1.0 - p)).ln();
+        1.0 / (1.0 + (-(a * logit + b)).exp())
+    }
+
+    fn coefficients(&self) -> (f32, f32) {
+        match *self {
+            Calibration::Temperature { temperature } => (1.0 / temperature, 0.0),
+            Calibration::Platt { a, b } => (a, b),
+        }
+    }
+}
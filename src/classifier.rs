@@ -0,0 +1,126 @@
+use crate::detector::{
+    BoundingBox, CAT_CLASS_ID, CatDetector, Detection, DetectorConfig, ModelInput, TensorLayout, input_dimensions,
+    load_session, resolve_layout, run_model,
+};
+use crate::error::{DetectError, Result};
+use image::DynamicImage;
+use ort::Session;
+use std::path::Path;
+
+/// Input size for classifiers that accept any size
+pub const CLASSIFIER_INPUT_SIZE: u32 = 224;
+
+/// A binary cat/not-cat image classifier: a quick yes or no on whether an
+/// image shows a cat, without locating it.
+///
+/// The model takes one NCHW (or NHWC) image, at the square size fixed in the model
+/// (otherwise [`CLASSIFIER_INPUT_SIZE`]), and outputs one score or two:
+///
+/// - `[p]`: the cat probability, or a logit when outside 0-1
+/// - `[p_other, p_cat]`: probabilities summing to 1, or logits (softmaxed)
+///
+/// A score above the confidence threshold comes back as a single cat
+/// [`Detection`] whose box is the whole image, so scans and their outputs
+/// work unchanged; crops, heatmaps and the like then cover the full image.
+pub struct CatClassifier {
+    session: Session,
+    config: DetectorConfig,
+    input_size: u32,
+}
+
+impl CatClassifier {
+    pub fn new(model_path: &Path, mut config: DetectorConfig) -> Result<Self> {
+        let session = load_session(model_path)?;
+        let layout = resolve_layout(&session, &config);
+        config.layout = Some(layout);
+        let input_size = match (layout, input_dimensions(&session)) {
+            (TensorLayout::Nchw, &[_, _, Some(height), Some(width)])
+            | (TensorLayout::Nhwc, &[_, Some(height), Some(width), _])
+                if height == width =>
+            {
+                height
+            }
+            _ => CLASSIFIER_INPUT_SIZE,
+        };
+
+        diag!("Classifier input size: {input_size}x{input_size}");
+        Ok(Self {
+            session,
+            config,
+            input_size,
+        })
+    }
+
+    /// Side of the square image the model takes
+    pub fn input_size(&self) -> u32 {
+        self.input_size
+    }
+}
+
+impl CatDetector for CatClassifier {
+    fn config(&self) -> &DetectorConfig {
+        &self.config
+    }
+
+    fn detect_input_with(&self, input: &ModelInput, config: &DetectorConfig) -> Result<Vec<Detection>> {
+        // Scans prepare inputs at the detector size; scale them to the classifier's
+        let resized;
+        let input = if input.input_size == self.input_size {
+            input
+        } else {
+            let image = DynamicImage::ImageRgb8(input.to_rgb_image(&config.normalization));
+            resized = ModelInput {
+                orig_width: input.orig_width,
+                orig_height: input.orig_height,
+                ..ModelInput::new(&image, self.input_size, config)
+            };
+            &resized
+        };
+
+        let outputs = run_model(&self.session, &input.tensor)?;
+        let output = outputs[0]
+            .try_extract::<f32>()
+            .map_err(DetectError::model("failed to extract output tensor"))?;
+        let scores: Vec<f32> = output.view().iter().copied().collect();
+
+        let Some(score) = cat_score(&scores) else {
+            diag!("Unexpected classifier output shape: {:?}", output.view().shape());
+            return Ok(Vec::new());
+        };
+
+        let (width, height) = (input.orig_width, input.orig_height);
+        let confidence = config.calibrate(score);
+        if confidence <= config.confidence_for(width, height) {
+            return Ok(Vec::new());
+        }
+        let Some(class_id) = config.report_class(CAT_CLASS_ID) else {
+            return Ok(Vec::new());
+        };
+
+        Ok(vec![Detection {
+            class_id,
+            confidence,
+            bbox: BoundingBox {
+                x1: 0.0,
+                y1: 0.0,
+                x2: width as f32,
+                y2: height as f32,
+            },
+        }])
+    }
+}
+
+/// Cat probability from a one- or two-score classifier output
+fn cat_score(scores: &[f32]) -> Option<f32> {
+    let is_probability = |score: f32| (0.0..=1.0).contains(&score);
+    let sigmoid = |logit: f32| 1.0 / (1.0 + (-logit).exp());
+
+    match *scores {
+        [cat] if is_probability(cat) => Some(cat),
+        [cat] => Some(sigmoid(cat)),
+        [other, cat] if is_probability(other) && is_probability(cat) && (other + cat - 1.0).abs() < 1e-3 => Some(cat),
+        // Softmax over two logits is the sigmoid of their difference
+        [other, cat] => Some(sigmoid(cat - other)),
+        _ => None,
+    }
+}
@@ -0,0 +1,113 @@
+use crate::detector::{Detection, YOLO_CLASSES};
+use serde::Serialize;
+use std::path::Path;
+
+/// Official COCO category ID of each of the 80 YOLO classes; the COCO
+/// annotations number 91 categories, of which 11 were never labeled
+pub const COCO_CATEGORY_IDS: [usize; 80] = [
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 27, 28, 31, 32, 33, 34,
+    35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63,
+    64, 65, 67, 70, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 84, 85, 86, 87, 88, 89, 90,
+];
+
+/// Detections across a scan in the COCO object detection format, for
+/// `--format coco`.
+///
+/// Images and annotations are numbered from 1 in the order they are added.
+/// Boxes are `[x, y, width, height]` in image pixels and categories carry
+/// the official COCO IDs (cat is 17), so the output loads directly into
+/// COCO tooling such as pycocotools.
+#[derive(Debug, Default, Serialize)]
+pub struct CocoDataset {
+    images: Vec<CocoImage>,
+    annotations: Vec<CocoAnnotation>,
+    categories: Vec<CocoCategory>,
+}
+
+#[derive(Debug, Serialize)]
+struct CocoImage {
+    id: usize,
+    file_name: String,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct CocoAnnotation {
+    id: usize,
+    image_id: usize,
+    category_id: usize,
+    bbox: [f32; 4],
+    area: f32,
+    iscrowd: u8,
+    score: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct CocoCategory {
+    id: usize,
+    name: &'static str,
+}
+
+impl CocoDataset {
+    /// An empty dataset whose categories are the YOLO `classes` searched for
+    pub fn new(classes: &[usize]) -> Self {
+        let mut classes = classes.to_vec();
+        classes.sort_unstable();
+        classes.dedup();
+
+        Self {
+            categories: classes
+                .into_iter()
+                .map(|class_id| CocoCategory {
+                    id: COCO_CATEGORY_IDS[class_id],
+                    name: YOLO_CLASSES[class_id],
+                })
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Add one `width`x`height` image and an annotation per detection
+    pub fn add(&mut self, path: &Path, width: u32, height: u32, detections: &[Detection]) {
+        let image_id = self.images.len() + 1;
+        self.images.push(CocoImage {
+            id: image_id,
+            file_name: path.display().to_string(),
+            width,
+            height,
+        });
+
+        for detection in detections {
+            let b = detection.bbox;
+            self.annotations.push(CocoAnnotation {
+                id: self.annotations.len() + 1,
+                image_id,
+                category_id: COCO_CATEGORY_IDS[detection.class_id],
+                bbox: [b.x1, b.y1, b.x2 - b.x1, b.y2 - b.y1],
+                area: b.area(),
+                iscrowd: 0,
+                score: detection.confidence,
+            });
+        }
+    }
+
+    /// Number of images added
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// The dataset as one JSON document, indented when `pretty`
+    pub fn to_json(&self, pretty: bool) -> String {
+        let json = if pretty {
+            serde_json::to_string_pretty(self)
+        } else {
+            serde_json::to_string(self)
+        };
+        json.expect("COCO datasets serialize")
+    }
+}
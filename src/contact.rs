@@ -0,0 +1,133 @@
+use crate::crop::crop_rect;
+use crate::error::{DetectError, Result};
+use crate::scan::ScanResult;
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+use std::path::{Path, PathBuf};
+
+/// Side of the square cell each crop is fitted into, in pixels
+pub const CONTACT_THUMBNAIL_SIZE: u32 = 160;
+
+/// Default number of thumbnails per row
+pub const DEFAULT_CONTACT_COLUMNS: u32 = 8;
+
+/// Rows per sheet before a new sheet is started
+const SHEET_ROWS: u32 = 12;
+
+/// Space between and around thumbnails, in pixels
+const GAP: u32 = 4;
+
+const BACKGROUND: Rgb<u8> = Rgb([32, 32, 32]);
+
+/// Tiles the best crop of each matched image into a grid for quick review.
+///
+/// Each crop is scaled to fit a [`CONTACT_THUMBNAIL_SIZE`] square, keeping its
+/// aspect ratio, and thumbnails fill the sheet row by row in the order they
+/// are added. A sheet holds `columns` x 12 thumbnails; once one is full it is
+/// written out and a new one started, so only a single sheet is ever held in
+/// memory. A run that fills one sheet writes exactly the requested file;
+/// longer runs number them `sheet-1.jpg`, `sheet-2.jpg` and so on.
+pub struct ContactSheet {
+    path: PathBuf,
+    columns: u32,
+    thumbnails: Vec<RgbImage>,
+    added: usize,
+    written: Vec<PathBuf>,
+}
+
+impl ContactSheet {
+    /// Sheets for `path`, whose extension picks the image format
+    pub fn new(path: &Path, columns: u32) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            columns: columns.max(1),
+            thumbnails: Vec::new(),
+            added: 0,
+            written: Vec::new(),
+        }
+    }
+
+    /// Add a thumbnail of the most confident detection in `result`; images
+    /// without detections are ignored
+    pub fn add(&mut self, result: &ScanResult) -> Result<()> {
+        let Some(best) = result.detections.iter().max_by(|a, b| a.confidence.total_cmp(&b.confidence)) else {
+            return Ok(());
+        };
+
+        let img = image::open(&result.path).map_err(|e| DetectError::image(&result.path, e))?;
+        let Some((x, y, width, height)) = crop_rect(&best.bbox, img.width(), img.height()) else {
+            return Ok(());
+        };
+
+        if self.thumbnails.len() == (self.columns * SHEET_ROWS) as usize {
+            self.write_sheet(true)?;
+        }
+
+        let thumbnail = img
+            .crop_imm(x, y, width, height)
+            .resize(CONTACT_THUMBNAIL_SIZE, CONTACT_THUMBNAIL_SIZE, FilterType::Triangle)
+            .to_rgb8();
+        self.thumbnails.push(thumbnail);
+        self.added += 1;
+        Ok(())
+    }
+
+    /// Write the last sheet and return every sheet written, in order. Nothing
+    /// is written when no thumbnails were added.
+    pub fn finish(mut self) -> Result<Vec<PathBuf>> {
+        if !self.thumbnails.is_empty() {
+            self.write_sheet(false)?;
+        }
+        Ok(self.written)
+    }
+
+    /// Number of thumbnails added so far, across all sheets
+    pub fn len(&self) -> usize {
+        self.added
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Write the current sheet; `more` when further sheets will follow
+    fn write_sheet(&mut self, more: bool) -> Result<()> {
+        let count = self.thumbnails.len() as u32;
+        let columns = self.columns.min(count);
+        let rows = count.div_ceil(self.columns);
+        let cell = CONTACT_THUMBNAIL_SIZE + GAP;
+
+        let mut sheet = RgbImage::from_pixel(columns * cell + GAP, rows * cell + GAP, BACKGROUND);
+        for (index, thumbnail) in (0..).zip(&self.thumbnails) {
+            // Center each thumbnail in its cell
+            let x = GAP + (index % self.columns) * cell + (CONTACT_THUMBNAIL_SIZE - thumbnail.width()) / 2;
+            let y = GAP + (index / self.columns) * cell + (CONTACT_THUMBNAIL_SIZE - thumbnail.height()) / 2;
+            imageops::replace(&mut sheet, thumbnail, i64::from(x), i64::from(y));
+        }
+
+        // A single sheet keeps the plain name
+        let target = if self.written.is_empty() && !more {
+            self.path.clone()
+        } else {
+            numbered(&self.path, self.written.len() + 1)
+        };
+
+        sheet.save(&target).map_err(|source| DetectError::Encode {
+            path: target.clone(),
+            source,
+        })?;
+        self.thumbnails.clear();
+        self.written.push(target);
+        Ok(())
+    }
+}
+
+/// `sheet.jpg` -> `sheet-1.jpg`
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{n}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{n}"),
+    };
+    path.with_file_name(name)
+}
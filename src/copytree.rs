@@ -0,0 +1,139 @@
+use crate::error::{DetectError, Result};
+use crate::hash::calculate_sha256;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// What [`TreeCopier::copy`] did with one file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CopyOutcome {
+    /// Copied to this path (or would be, in a dry run)
+    Copied(PathBuf),
+    /// An identical file was already at this path
+    Unchanged(PathBuf),
+}
+
+/// Copies files into a mirror of the scanned directory tree.
+///
+/// Each file keeps its path relative to the scan root, with intermediate
+/// directories created as needed. Nothing in the destination is ever
+/// overwritten: a file identical to the source counts as already copied, and
+/// a different file at the target path makes the copy go to `name-1.ext`,
+/// `name-2.ext` and so on. Copies are written to a temporary file and renamed
+/// into place, so an interrupted run leaves no partial files behind.
+pub struct TreeCopier {
+    root: PathBuf,
+    dest: PathBuf,
+    dry_run: bool,
+}
+
+impl TreeCopier {
+    /// Mirror files under `root` into `dest`; with `dry_run`, only work out
+    /// where they would go. `dest` must not lie inside `root`, or the scan
+    /// would pick up its own copies.
+    pub fn new(root: &Path, dest: &Path, dry_run: bool) -> Result<Self> {
+        let absolute = |path: &Path| fs::canonicalize(path).or_else(|_| std::path::absolute(path));
+        if root.is_dir()
+            && let (Ok(root), Ok(dest)) = (absolute(root), absolute(dest))
+            && dest.starts_with(&root)
+        {
+            return Err(DetectError::InvalidInput {
+                path: dest,
+                message: format!("copy destination is inside the scanned directory {}", root.display()),
+            });
+        }
+
+        if !dry_run {
+            fs::create_dir_all(dest).map_err(DetectError::io("failed to create copy destination", dest))?;
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            dest: dest.to_path_buf(),
+            dry_run,
+        })
+    }
+
+    /// Copy `path` to its place in the mirror tree
+    pub fn copy(&self, path: &Path) -> Result<CopyOutcome> {
+        let target = self.dest.join(self.relative_path(path));
+
+        for candidate in (0..).map(|n| numbered(&target, n)) {
+            if !candidate.exists() {
+                if !self.dry_run {
+                    copy_atomic(path, &candidate)?;
+                }
+                return Ok(CopyOutcome::Copied(candidate));
+            }
+
+            if same_content(path, &candidate)? {
+                return Ok(CopyOutcome::Unchanged(candidate));
+            }
+        }
+
+        unreachable!("numbered candidates are endless")
+    }
+
+    /// `path` relative to the scan root, reduced to plain names so it cannot
+    /// escape the destination. Paths outside the root (e.g. from a path
+    /// list) keep their full path below the destination.
+    fn relative_path(&self, path: &Path) -> PathBuf {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let relative: PathBuf = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        // Scanning a single file leaves nothing after the root
+        match (relative.as_os_str().is_empty(), path.file_name()) {
+            (true, Some(name)) => PathBuf::from(name),
+            _ => relative,
+        }
+    }
+}
+
+/// `photo.jpg`, then `photo-1.jpg`, `photo-2.jpg`, ...
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    if n == 0 {
+        return path.to_path_buf();
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{n}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{n}"),
+    };
+    path.with_file_name(name)
+}
+
+fn same_content(a: &Path, b: &Path) -> Result<bool> {
+    let size = |path: &Path| {
+        fs::metadata(path)
+            .map(|metadata| metadata.len())
+            .map_err(DetectError::io("failed to read metadata", path))
+    };
+
+    Ok(size(a)? == size(b)? && calculate_sha256(a)? == calculate_sha256(b)?)
+}
+
+fn copy_atomic(source: &Path, target: &Path) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(DetectError::io("failed to create directory", parent))?;
+    }
+
+    let mut temp = target.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+
+    fs::copy(source, &temp).map_err(DetectError::io("failed to copy file", source))?;
+    fs::rename(&temp, target).map_err(|source| {
+        let _ = fs::remove_file(&temp);
+        DetectError::Io {
+            context: "failed to move copy into place",
+            path: target.to_path_buf(),
+            source,
+        }
+    })
+}
@@ -0,0 +1,210 @@
+use crate::detector::BoundingBox;
+use crate::error::{DetectError, Result};
+use crate::hash::calculate_sha256;
+use crate::scan::ScanResult;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, imageops};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// JPEG quality of crops unless chosen otherwise, the `image` crate's default
+pub const DEFAULT_CROP_QUALITY: u8 = 75;
+
+/// File format crops are encoded in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CropFormat {
+    /// Lossy JPEG at a quality from 1 to 100
+    Jpeg { quality: u8 },
+    /// Lossless PNG, e.g. for training data
+    Png,
+    /// Lossless WebP
+    WebP,
+}
+
+impl Default for CropFormat {
+    fn default() -> Self {
+        CropFormat::Jpeg {
+            quality: DEFAULT_CROP_QUALITY,
+        }
+    }
+}
+
+impl CropFormat {
+    /// File name extension of the format, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            CropFormat::Jpeg { .. } => "jpg",
+            CropFormat::Png => "png",
+            CropFormat::WebP => "webp",
+        }
+    }
+}
+
+/// Counts of crops handled for one image
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CropStats {
+    pub written: usize,
+    /// Crops whose file already existed and were left untouched
+    pub skipped: usize,
+}
+
+/// Writes one image per detection into a directory, idempotently across runs.
+///
+/// Crop filenames are derived from the source file's SHA-256 and the box
+/// index, so re-running a scan maps each crop to the same file. Existing crops
+/// are skipped unless overwriting is enabled, and every crop is written to a
+/// temporary file first and renamed into place so an interrupted run never
+/// leaves a truncated image behind. Crops are JPEGs unless another
+/// [`CropFormat`] is chosen with [`CropWriter::with_format`].
+///
+/// Crops are tight around each box, or with `square` expanded to a square
+/// around it (see [`square_rect`]).
+pub struct CropWriter {
+    dir: PathBuf,
+    overwrite: bool,
+    square: bool,
+    format: CropFormat,
+}
+
+impl CropWriter {
+    pub fn new(dir: &Path, overwrite: bool, square: bool) -> Result<Self> {
+        fs::create_dir_all(dir).map_err(DetectError::io("failed to create crop directory", dir))?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            overwrite,
+            square,
+            format: CropFormat::default(),
+        })
+    }
+
+    /// Encode crops as `format` instead of JPEG at [`DEFAULT_CROP_QUALITY`]
+    pub fn with_format(mut self, format: CropFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Write crops for every detection in `result`
+    pub fn write_crops(&self, result: &ScanResult) -> Result<CropStats> {
+        let mut stats = CropStats::default();
+
+        if result.detections.is_empty() {
+            return Ok(stats);
+        }
+
+        let sha256 = match &result.sha256 {
+            Some(hash) => hash.clone(),
+            None => calculate_sha256(&result.path)?,
+        };
+
+        // Only decode the source when at least one crop still has to be written
+        let mut image: Option<DynamicImage> = None;
+
+        for (index, detection) in result.detections.iter().enumerate() {
+            let target = self.dir.join(crop_file_name(&sha256, index, self.format));
+
+            if !self.overwrite && target.exists() {
+                stats.skipped += 1;
+                continue;
+            }
+
+            if image.is_none() {
+                image = Some(image::open(&result.path).map_err(|e| DetectError::image(&result.path, e))?);
+            }
+            let img = image.as_ref().unwrap();
+
+            let Some((x, y, width, height)) = crop_rect(&detection.bbox, img.width(), img.height())
+            else {
+                continue;
+            };
+
+            let crop = if self.square {
+                let (x, y, side) = square_rect(&detection.bbox, img.width(), img.height());
+                // Black padding wherever the square extends past the image
+                let mut canvas = DynamicImage::new_rgb8(side, side);
+                imageops::replace(&mut canvas, img, -x, -y);
+                canvas
+            } else {
+                DynamicImage::ImageRgb8(img.crop_imm(x, y, width, height).to_rgb8())
+            };
+            write_atomic(&crop, &target, self.format)?;
+            stats.written += 1;
+        }
+
+        Ok(stats)
+    }
+}
+
+/// Deterministic crop filename: first 16 hex digits of the source hash plus
+/// box index, with the extension of `format`
+pub fn crop_file_name(sha256: &str, index: usize, format: CropFormat) -> String {
+    format!("{}_{index}.{}", &sha256[..16.min(sha256.len())], format.extension())
+}
+
+/// Clamp a bounding box to the image, returning `(x, y, width, height)` or
+/// `None` when nothing of the box lies inside the image
+pub fn crop_rect(bbox: &BoundingBox, image_width: u32, image_height: u32) -> Option<(u32, u32, u32, u32)> {
+    let x1 = bbox.x1.max(0.0).min(image_width as f32) as u32;
+    let y1 = bbox.y1.max(0.0).min(image_height as f32) as u32;
+    let x2 = bbox.x2.max(0.0).min(image_width as f32).ceil() as u32;
+    let y2 = bbox.y2.max(0.0).min(image_height as f32).ceil() as u32;
+
+    if x2 <= x1 || y2 <= y1 {
+        return None;
+    }
+
+    Some((x1, y1, x2 - x1, y2 - y1))
+}
+
+/// Square around a bounding box, as `(x, y, side)`: the box's longer side,
+/// centered on the box and shifted to stay inside the image. A square larger
+/// than the image in one dimension is centered on the box along it, so `x` or
+/// `y` is negative and the crop needs padding.
+pub fn square_rect(bbox: &BoundingBox, image_width: u32, image_height: u32) -> (i64, i64, u32) {
+    let side = (bbox.x2 - bbox.x1).max(bbox.y2 - bbox.y1).ceil().max(1.0) as u32;
+
+    let place = |center: f32, extent: u32| {
+        let start = (center - side as f32 / 2.0).round() as i64;
+        let slack = i64::from(extent) - i64::from(side);
+        start.clamp(slack.min(0), slack.max(0))
+    };
+
+    let x = place((bbox.x1 + bbox.x2) / 2.0, image_width);
+    let y = place((bbox.y1 + bbox.y2) / 2.0, image_height);
+    (x, y, side)
+}
+
+/// Encode as `format` to a sibling temp file, then rename over `target`
+fn write_atomic(img: &DynamicImage, target: &Path, format: CropFormat) -> Result<()> {
+    let file_name = target
+        .file_name()
+        .expect("crop paths end in a file name")
+        .to_string_lossy();
+    let temp = target.with_file_name(format!(".{file_name}.tmp"));
+
+    let file = File::create(&temp).map_err(DetectError::io("failed to create crop", &temp))?;
+    let mut writer = BufWriter::new(file);
+    let encoded = match format {
+        CropFormat::Jpeg { quality } => img.write_with_encoder(JpegEncoder::new_with_quality(&mut writer, quality)),
+        CropFormat::Png => img.write_with_encoder(PngEncoder::new(&mut writer)),
+        CropFormat::WebP => img.write_with_encoder(WebPEncoder::new_lossless(&mut writer)),
+    };
+    let written = encoded
+        .map_err(|source| DetectError::Encode { path: temp.clone(), source })
+        .and_then(|()| writer.flush().map_err(DetectError::io("failed to write crop", &temp)));
+    if let Err(e) = written {
+        drop(writer);
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+
+    if let Err(e) = fs::rename(&temp, target) {
+        let _ = fs::remove_file(&temp);
+        return Err(DetectError::io("failed to move crop into place", target)(e));
+    }
+
+    Ok(())
+}
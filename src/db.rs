@@ -0,0 +1,175 @@
+use crate::detector::Detection;
+use crate::error::{DetectError, Result};
+use chrono::Local;
+use rusqlite::{Connection, params};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Images written per transaction
+const BATCH_SIZE: usize = 100;
+
+/// Scan results stored in SQLite: one row per image in `images` and one per
+/// detection in `detections`.
+///
+/// Rows are written as images are processed and committed in batches, so an
+/// interrupted scan keeps everything up to the last batch. Rescanning an
+/// image replaces its earlier rows. An image without a cat still gets its
+/// `images` row (with 0 detections), so it can be told apart from one that
+/// was never scanned. A third table, `scan_cache`, records each image's
+/// canonical path and modification time under the settings it was scanned
+/// with, so later runs with the same settings can skip images that have not
+/// changed.
+pub struct ResultDb {
+    conn: Connection,
+    pending: usize,
+}
+
+impl ResultDb {
+    /// Open (or create) the database and its tables
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path).map_err(DetectError::database("failed to open SQLite database"))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS detections (
+                 path TEXT NOT NULL,
+                 sha256 TEXT,
+                 class TEXT NOT NULL,
+                 confidence REAL NOT NULL,
+                 x REAL NOT NULL,
+                 y REAL NOT NULL,
+                 w REAL NOT NULL,
+                 h REAL NOT NULL,
+                 scanned_at TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS detections_path ON detections (path);
+             CREATE TABLE IF NOT EXISTS images (
+                 path TEXT PRIMARY KEY,
+                 sha256 TEXT,
+                 detections INTEGER NOT NULL,
+                 scanned_at TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS scan_cache (
+                 path TEXT NOT NULL,
+                 settings TEXT NOT NULL,
+                 mtime INTEGER NOT NULL,
+                 PRIMARY KEY (path, settings)
+             );",
+        )
+        .map_err(DetectError::database("failed to initialize SQLite database"))?;
+
+        Ok(Self { conn, pending: 0 })
+    }
+
+    /// Modification time (nanoseconds since the epoch) of every image
+    /// already scanned with `settings`, by canonical path
+    pub fn known_files(&self, settings: &str) -> Result<HashMap<PathBuf, i64>> {
+        let mut query = self
+            .conn
+            .prepare("SELECT path, mtime FROM scan_cache WHERE settings = ?1")
+            .map_err(DetectError::database("failed to query scanned files"))?;
+
+        let rows = query
+            .query_map(params![settings], |row| {
+                Ok((PathBuf::from(row.get::<_, String>(0)?), row.get(1)?))
+            })
+            .map_err(DetectError::database("failed to query scanned files"))?;
+
+        rows.collect::<rusqlite::Result<_>>()
+            .map_err(DetectError::database("failed to read scanned files"))
+    }
+
+    /// Replace the rows for `path` with its `images` row and `detections`,
+    /// noting the file's modification time under `settings`, a description
+    /// of everything that affects the result (model, thresholds, classes)
+    /// for [`ResultDb::known_files`]. Starts a transaction when none is
+    /// open; every 100 images it is committed.
+    pub fn record(
+        &mut self,
+        path: &Path,
+        mtime: Option<i64>,
+        settings: &str,
+        sha256: Option<&str>,
+        detections: &[Detection],
+    ) -> Result<()> {
+        let canonical = fs::canonicalize(path).ok();
+        let path = path.display().to_string();
+        let scanned_at = Local::now().to_rfc3339();
+
+        if self.conn.is_autocommit() {
+            self.conn
+                .execute_batch("BEGIN;")
+                .map_err(DetectError::database("failed to start transaction"))?;
+        }
+
+        self.conn
+            .execute("DELETE FROM detections WHERE path = ?1", params![path])
+            .map_err(DetectError::database("failed to clear previous detections"))?;
+
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO images (path, sha256, detections, scanned_at) VALUES (?1, ?2, ?3, ?4)",
+                params![path, sha256, detections.len() as i64, scanned_at],
+            )
+            .map_err(DetectError::database("failed to record image"))?;
+
+        if let (Some(canonical), Some(mtime)) = (canonical, mtime) {
+            self.conn
+                .execute(
+                    "INSERT OR REPLACE INTO scan_cache (path, settings, mtime) VALUES (?1, ?2, ?3)",
+                    params![canonical.display().to_string(), settings, mtime],
+                )
+                .map_err(DetectError::database("failed to record scanned file"))?;
+        }
+
+        let mut insert = self
+            .conn
+            .prepare_cached(
+                "INSERT INTO detections (path, sha256, class, confidence, x, y, w, h, scanned_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )
+            .map_err(DetectError::database("failed to prepare insert"))?;
+
+        for detection in detections {
+            let bbox = detection.bbox;
+            insert
+                .execute(params![
+                    path,
+                    sha256,
+                    detection.class_name(),
+                    detection.confidence,
+                    bbox.x1,
+                    bbox.y1,
+                    bbox.x2 - bbox.x1,
+                    bbox.y2 - bbox.y1,
+                    scanned_at,
+                ])
+                .map_err(DetectError::database("failed to insert detection"))?;
+        }
+        drop(insert);
+
+        self.pending += 1;
+        if self.pending >= BATCH_SIZE {
+            self.commit()?;
+        }
+
+        Ok(())
+    }
+
+    /// Commit any rows still pending
+    pub fn finish(mut self) -> Result<()> {
+        self.commit()
+    }
+
+    /// Commit the open transaction, if any
+    fn commit(&mut self) -> Result<()> {
+        if !self.conn.is_autocommit() {
+            self.conn
+                .execute_batch("COMMIT;")
+                .map_err(DetectError::database("failed to commit detections"))?;
+        }
+        self.pending = 0;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use crate::error::{DetectError, Result};
+use exif::{In, Tag};
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, DynamicImage, GrayImage, ImageDecoder, ImageReader, RgbImage};
+use jpeg_decoder::PixelFormat;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Most frames decoded from one animation
+pub const MAX_ANIMATION_FRAMES: usize = 32;
+
+/// Open and decode an image, refusing anything over `max_pixels`.
+///
+/// The dimensions are read from the header first, so an oversized file (or a
+/// decompression bomb) is rejected with [`DetectError::TooLarge`] without
+/// allocating its pixel buffer. The decoder checks the pixel count of the
+/// header it decodes from again as a backstop. With no limit this behaves
+/// like [`image::open`].
+pub fn open_image(path: &Path, max_pixels: Option<u64>) -> Result<DynamicImage> {
+    let Some(max_pixels) = max_pixels else {
+        return image::open(path).map_err(|e| DetectError::image(path, e));
+    };
+
+    check_pixels(path, max_pixels)?;
+
+    let decoder = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(DetectError::io("failed to open image", path))?
+        .into_decoder()
+        .map_err(|e| DetectError::image(path, e))?;
+    let (width, height) = decoder.dimensions();
+    check_size(path, width, height, max_pixels)?;
+
+    DynamicImage::from_decoder(decoder).map_err(|e| DetectError::image(path, e))
+}
+
+/// Decode up to `max_frames` frames of an animated GIF, each composited to
+/// the full canvas. Other formats yield their single image.
+pub fn open_frames(path: &Path, max_pixels: Option<u64>, max_frames: usize) -> Result<Vec<DynamicImage>> {
+    if !is_animated_format(path) {
+        return Ok(vec![open_image(path, max_pixels)?]);
+    }
+
+    if let Some(max_pixels) = max_pixels {
+        check_pixels(path, max_pixels)?;
+    }
+
+    let file = File::open(path).map_err(DetectError::io("failed to open image", path))?;
+    let decoder = GifDecoder::new(BufReader::new(file)).map_err(|e| DetectError::image(path, e))?;
+
+    let frames = decoder
+        .into_frames()
+        .take(max_frames.max(1))
+        .map(|frame| frame.map(|frame| DynamicImage::ImageRgba8(frame.into_buffer())))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| DetectError::image(path, e))?;
+
+    if frames.is_empty() {
+        return Err(DetectError::InvalidInput {
+            path: path.to_path_buf(),
+            message: "animation has no frames".to_string(),
+        });
+    }
+
+    Ok(frames)
+}
+
+/// The small preview image many cameras embed in a JPEG's EXIF block, or
+/// `None` when the file has none (or it cannot be read)
+pub fn exif_thumbnail(path: &Path) -> Option<DynamicImage> {
+    let file = File::open(path).ok()?;
+    let exif = exif::Reader::new().read_from_container(&mut BufReader::new(file)).ok()?;
+
+    // The thumbnail's offset is relative to the start of the EXIF (TIFF) data
+    let field = |tag| exif.get_field(tag, In::THUMBNAIL)?.value.get_uint(0);
+    let offset = field(Tag::JPEGInterchangeFormat)? as usize;
+    let length = field(Tag::JPEGInterchangeFormatLength)? as usize;
+    let bytes = exif.buf().get(offset..offset.checked_add(length)?)?;
+
+    image::load_from_memory(bytes).ok()
+}
+
+/// Decode a JPEG at the smallest DCT scale (1/8, 1/4 or 1/2) that keeps both
+/// sides at least `min_side`, returning the image and the file's full size.
+///
+/// Scaled decoding skips most of the inverse DCT, so a large photo that will
+/// only be shrunk to the model input decodes several times faster. `None`
+/// when the photo is too small to scale, or is a JPEG variant this decoder
+/// does not scale (CMYK, 16-bit, or unreadable); decode those with
+/// [`open_image`], which also reports any errors.
+pub fn open_jpeg_scaled(path: &Path, max_pixels: Option<u64>, min_side: u32) -> Result<Option<(DynamicImage, (u32, u32))>> {
+    if let Some(max_pixels) = max_pixels {
+        check_pixels(path, max_pixels)?;
+    }
+
+    let file = File::open(path).map_err(DetectError::io("failed to open image", path))?;
+    let mut decoder = jpeg_decoder::Decoder::new(BufReader::new(file));
+    if decoder.read_info().is_err() {
+        return Ok(None);
+    }
+    let Some(info) = decoder.info() else {
+        return Ok(None);
+    };
+
+    let (width, height) = (u32::from(info.width), u32::from(info.height));
+    if width.min(height) < min_side.saturating_mul(2) {
+        return Ok(None);
+    }
+
+    // The decoder settles for a scale once either side reaches its request,
+    // so only the shorter side gets one
+    let min_side = u16::try_from(min_side).unwrap_or(u16::MAX);
+    let request = if width >= height { (u16::MAX, min_side) } else { (min_side, u16::MAX) };
+    let Ok((scaled_width, scaled_height)) = decoder.scale(request.0, request.1) else {
+        return Ok(None);
+    };
+    let Ok(pixels) = decoder.decode() else {
+        return Ok(None);
+    };
+
+    let (scaled_width, scaled_height) = (u32::from(scaled_width), u32::from(scaled_height));
+    let image = match info.pixel_format {
+        PixelFormat::L8 => GrayImage::from_raw(scaled_width, scaled_height, pixels).map(DynamicImage::ImageLuma8),
+        PixelFormat::RGB24 => RgbImage::from_raw(scaled_width, scaled_height, pixels).map(DynamicImage::ImageRgb8),
+        PixelFormat::L16 | PixelFormat::CMYK32 => None,
+    };
+
+    Ok(image.map(|image| (image, (width, height))))
+}
+
+/// Whether [`open_jpeg_scaled`] applies to this file
+pub fn is_jpeg(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"))
+}
+
+/// Whether [`open_frames`] decodes more than the first frame of this file
+pub fn is_animated_format(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif"))
+}
+
+/// Reject a file whose header reports more than `max_pixels` pixels
+fn check_pixels(path: &Path, max_pixels: u64) -> Result<()> {
+    let (width, height) = image::image_dimensions(path).map_err(|e| DetectError::image(path, e))?;
+    check_size(path, width, height, max_pixels)
+}
+
+fn check_size(path: &Path, width: u32, height: u32, max_pixels: u64) -> Result<()> {
+    if u64::from(width) * u64::from(height) > max_pixels {
+        return Err(DetectError::TooLarge {
+            path: path.to_path_buf(),
+            width,
+            height,
+            max_pixels,
+        });
+    }
+
+    Ok(())
+}
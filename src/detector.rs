@@ -0,0 +1,1096 @@
+use crate::calibration::Calibration;
+use crate::error::{DetectError, Result};
+use crate::mask::IgnoreMask;
+use crate::trace::span;
+use image::{DynamicImage, Rgb, RgbImage};
+use image::imageops::FilterType;
+use ndarray::{Array, ArrayD, ArrayView2, ArrayViewD, Axis, IxDyn, Slice};
+use ort::{Environment, ExecutionProvider, Session, SessionBuilder, Value};
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::Arc;
+
+// YOLO COCO class names
+pub const YOLO_CLASSES: [&str; 80] = [
+    "person", "bicycle", "car", "motorcycle", "airplane", "bus", "train", "truck", "boat",
+    "traffic light", "fire hydrant", "stop sign", "parking meter", "bench", "bird", "cat",
+    "dog", "horse", "sheep", "cow", "elephant", "bear", "zebra", "giraffe", "backpack",
+    "umbrella", "handbag", "tie", "suitcase", "frisbee", "skis", "snowboard", "sports ball",
+    "kite", "baseball bat", "baseball glove", "skateboard", "surfboard", "tennis racket",
+    "bottle", "wine glass", "cup", "fork", "knife", "spoon", "bowl", "banana", "apple",
+    "sandwich", "orange", "broccoli", "carrot", "hot dog", "pizza", "donut", "cake", "chair",
+    "couch", "potted plant", "bed", "dining table", "toilet", "tv", "laptop", "mouse", "remote",
+    "keyboard", "cell phone", "microwave", "oven", "toaster", "sink", "refrigerator", "book",
+    "clock", "vase", "scissors", "teddy bear", "hair drier", "toothbrush"
+];
+
+pub const CAT_CLASS_ID: usize = 15;  // Index of "cat" in YOLO classes
+
+/// The COCO animal classes, bird (14) through giraffe (23)
+pub const ANIMAL_CLASS_IDS: [usize; 10] = [14, 15, 16, 17, 18, 19, 20, 21, 22, 23];
+
+/// Square input size (pixels) the YOLOv8 model expects
+pub const INPUT_SIZE: u32 = 640;
+
+/// Per-channel normalization applied as `(pixel / 255 - mean) / std`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Normalization {
+    pub mean: [f32; 3],
+    pub std: [f32; 3],
+}
+
+impl Normalization {
+    /// ImageNet channel statistics used by most torchvision-style training pipelines
+    pub const IMAGENET: Self = Self {
+        mean: [0.485, 0.456, 0.406],
+        std: [0.229, 0.224, 0.225],
+    };
+
+    /// Raw 0-255 pixel values, for models that scale their input inside the
+    /// graph; the `/255` is undone by a matching std
+    pub const RAW: Self = Self {
+        mean: [0.0; 3],
+        std: [1.0 / 255.0; 3],
+    };
+}
+
+impl Default for Normalization {
+    /// Plain `/255` scaling, as expected by Ultralytics exports
+    fn default() -> Self {
+        Self { mean: [0.0; 3], std: [1.0; 3] }
+    }
+}
+
+/// Order of the color channels in the model input
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChannelOrder {
+    /// Red, green, blue, as expected by Ultralytics exports
+    #[default]
+    Rgb,
+    /// Blue, green, red, as in models trained on OpenCV-loaded images
+    Bgr,
+}
+
+impl ChannelOrder {
+    /// Color (0 = red, 1 = green, 2 = blue) stored in input channel `channel`
+    fn color(self, channel: usize) -> usize {
+        match self {
+            ChannelOrder::Rgb => channel,
+            ChannelOrder::Bgr => 2 - channel,
+        }
+    }
+}
+
+/// Axis order of the image tensor fed to the model
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TensorLayout {
+    /// Batch, channels, height, width, as in PyTorch and Ultralytics exports
+    #[default]
+    Nchw,
+    /// Batch, height, width, channels, as in TensorFlow and Keras exports
+    Nhwc,
+}
+
+impl TensorLayout {
+    /// Layout of a model input with these dimensions, judged by where its
+    /// 3 color channels are: second means NCHW, last means NHWC. `None`
+    /// when that is ambiguous, e.g. dynamic or other channel counts.
+    pub fn detect(dimensions: &[Option<u32>]) -> Option<Self> {
+        match *dimensions {
+            [_, second, _, last] => match (second == Some(3), last == Some(3)) {
+                (true, false) => Some(TensorLayout::Nchw),
+                (false, true) => Some(TensorLayout::Nhwc),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Axis of the channels in a tensor of this layout
+    pub fn channel_axis(self) -> usize {
+        match self {
+            TensorLayout::Nchw => 1,
+            TensorLayout::Nhwc => 3,
+        }
+    }
+
+    /// Shape of a single `size` x `size` image with `channels` channels
+    fn shape(self, channels: usize, size: usize) -> [usize; 4] {
+        match self {
+            TensorLayout::Nchw => [1, channels, size, size],
+            TensorLayout::Nhwc => [1, size, size, channels],
+        }
+    }
+
+    /// Index of `channel` of pixel (`x`, `y`)
+    fn index(self, channel: usize, y: usize, x: usize) -> [usize; 4] {
+        match self {
+            TensorLayout::Nchw => [0, channel, y, x],
+            TensorLayout::Nhwc => [0, y, x, channel],
+        }
+    }
+}
+
+/// Axis-aligned box in original image pixel coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+}
+
+impl BoundingBox {
+    pub fn area(&self) -> f32 {
+        (self.x2 - self.x1).max(0.0) * (self.y2 - self.y1).max(0.0)
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        ((self.x1 + self.x2) / 2.0, (self.y1 + self.y2) / 2.0)
+    }
+
+    /// Intersection over union with another box
+    pub fn iou(&self, other: &Self) -> f32 {
+        let intersection = Self {
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+            x2: self.x2.min(other.x2),
+            y2: self.y2.min(other.y2),
+        }
+        .area();
+        let union = self.area() + other.area() - intersection;
+
+        if union > 0.0 { intersection / union } else { 0.0 }
+    }
+
+    /// Map a box found in a horizontally flipped image of width `width`
+    /// back onto the unflipped image
+    pub fn unflip(self, width: u32) -> Self {
+        let w = width as f32;
+        Self { x1: w - self.x2, x2: w - self.x1, ..self }
+    }
+
+    /// Map a box found in an image rotated clockwise by `degrees` back onto
+    /// the unrotated image of size `width` x `height`
+    pub fn unrotate(self, degrees: u32, width: u32, height: u32) -> Self {
+        let (w, h) = (width as f32, height as f32);
+        match degrees {
+            90 => Self { x1: self.y1, y1: h - self.x2, x2: self.y2, y2: h - self.x1 },
+            180 => Self { x1: w - self.x2, y1: h - self.y2, x2: w - self.x1, y2: h - self.y1 },
+            270 => Self { x1: w - self.y2, y1: self.x1, x2: w - self.y1, y2: self.x2 },
+            _ => self,
+        }
+    }
+}
+
+/// A single detected object
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Detection {
+    pub class_id: usize,
+    pub confidence: f32,
+    pub bbox: BoundingBox,
+}
+
+impl Detection {
+    /// COCO name of the detected class
+    pub fn class_name(&self) -> &'static str {
+        YOLO_CLASSES.get(self.class_id).copied().unwrap_or("unknown")
+    }
+}
+
+/// Look up a class by COCO name (case-insensitive) or numeric ID
+pub fn class_id(name: &str) -> Option<usize> {
+    let name = name.trim();
+    if let Ok(id) = name.parse::<usize>() {
+        return (id < YOLO_CLASSES.len()).then_some(id);
+    }
+
+    YOLO_CLASSES.iter().position(|class| class.eq_ignore_ascii_case(name))
+}
+
+/// Names of the ONNX Runtime execution providers usable in this build
+pub fn available_execution_providers() -> Vec<&'static str> {
+    execution_providers()
+        .into_iter()
+        .filter_map(|(name, available)| available.then_some(name))
+        .collect()
+}
+
+/// Every execution provider ONNX Runtime offers, with whether the loaded
+/// runtime library was built with it. A provider listed as available can
+/// still fail at session creation (no GPU, driver mismatch); see
+/// [`probe_execution_provider`].
+pub fn execution_providers() -> Vec<(&'static str, bool)> {
+    all_execution_providers()
+        .iter()
+        .map(|provider| (provider.as_str(), provider.is_available()))
+        .collect()
+}
+
+/// Start a throwaway session for the model at `model_path` on the execution
+/// provider `name` (as listed by [`execution_providers`]), to find out
+/// whether it works on this machine rather than just in this build
+pub fn probe_execution_provider(name: &str, model_path: &Path) -> Result<()> {
+    let provider = all_execution_providers()
+        .into_iter()
+        .find(|provider| provider.as_str() == name)
+        .ok_or_else(|| DetectError::InvalidInput {
+            path: model_path.to_path_buf(),
+            message: format!("unknown execution provider '{name}'"),
+        })?;
+
+    let environment = Arc::new(
+        Environment::builder()
+            .with_name("cat_detector_probe")
+            .build()
+            .map_err(DetectError::model("failed to create ONNX Runtime environment"))?
+    );
+
+    SessionBuilder::new(&environment)
+        .and_then(|builder| builder.with_execution_providers([provider]))
+        .and_then(|builder| builder.with_model_from_file(model_path))
+        .map(drop)
+        .map_err(DetectError::model(format!("failed to start a {name} session")))
+}
+
+fn all_execution_providers() -> [ExecutionProvider; 7] {
+    [
+        ExecutionProvider::CPU(Default::default()),
+        ExecutionProvider::CUDA(Default::default()),
+        ExecutionProvider::TensorRT(Default::default()),
+        ExecutionProvider::OpenVINO(Default::default()),
+        ExecutionProvider::ROCm(Default::default()),
+        ExecutionProvider::CoreML(Default::default()),
+        ExecutionProvider::DirectML(Default::default()),
+    ]
+}
+
+/// Tunable settings for [`YoloCatDetector`]
+#[derive(Clone, Debug)]
+pub struct DetectorConfig {
+    /// Minimum class score for a detection to count (0.0-1.0)
+    pub confidence: f32,
+    /// Overlap above which same-class boxes are merged by non-maximum suppression
+    pub iou_threshold: f32,
+    /// COCO class IDs to report (see [`YOLO_CLASSES`])
+    pub classes: Vec<usize>,
+    /// Classes the model is known to confuse with others: detections of the
+    /// first class are reported as the second (e.g. teddy bear as cat)
+    pub class_aliases: Vec<(usize, usize)>,
+    /// Classes whose detections are dropped, checked before aliasing
+    pub suppressed_classes: Vec<usize>,
+    /// Transform applied to scores before thresholding; reported
+    /// confidences are calibrated too
+    pub calibration: Option<Calibration>,
+    /// Per-color normalization; `mean` and `std` stay in red, green, blue
+    /// order whatever the [`DetectorConfig::channel_order`]
+    pub normalization: Normalization,
+    pub channel_order: ChannelOrder,
+    /// Axis order of the image tensor; `None` takes it from the shape of
+    /// the model's input where that is unambiguous (see
+    /// [`TensorLayout::detect`]), else NCHW
+    pub layout: Option<TensorLayout>,
+    /// Filter used to resize images to the model input size
+    pub resize_filter: FilterType,
+    /// Print the N highest-scoring anchors of each image to stderr (0 disables)
+    pub debug_top_k: usize,
+    /// Only include anchors scoring above this in the debug dump
+    pub debug_min_conf: f32,
+    /// Scale [`DetectorConfig::confidence`] with image resolution; see
+    /// [`DetectorConfig::confidence_for`]
+    pub adaptive_confidence: bool,
+    /// Model input the image is fed to (default: the first). ONNX Runtime
+    /// takes inputs by position, so the model must have no other inputs
+    pub input_name: Option<String>,
+    /// Model output holding the predictions (default: the first)
+    pub output_name: Option<String>,
+    /// Stop decoding a raw YOLOv8 head once this many distinct detections
+    /// (not overlapping beyond `iou_threshold`) qualify. Enough to answer
+    /// "is there a cat" or "are there N", but the anchors after the stop are
+    /// never looked at, so the boxes kept need not be the most confident.
+    pub stop_at: Option<usize>,
+    /// Feed zeros to the input channels of a model that takes more than the
+    /// three color channels (e.g. RGB plus depth) instead of refusing it
+    pub zero_fill_channels: bool,
+    /// After NMS, treat same-class boxes whose centers are at most this many
+    /// pixels (in the original image) apart as one object; see [`merge_nearby`]
+    pub merge_distance: Option<f32>,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        Self {
+            confidence: 0.25,
+            iou_threshold: 0.45,
+            classes: vec![CAT_CLASS_ID],
+            class_aliases: Vec::new(),
+            suppressed_classes: Vec::new(),
+            calibration: None,
+            normalization: Normalization::default(),
+            channel_order: ChannelOrder::default(),
+            layout: None,
+            resize_filter: FilterType::Triangle,
+            debug_top_k: 0,
+            debug_min_conf: 0.3,
+            adaptive_confidence: false,
+            input_name: None,
+            output_name: None,
+            stop_at: None,
+            zero_fill_channels: false,
+            merge_distance: None,
+        }
+    }
+}
+
+impl DetectorConfig {
+    /// `score` after [`DetectorConfig::calibration`], if any
+    pub fn calibrate(&self, score: f32) -> f32 {
+        self.calibration.map_or(score, |calibration| calibration.apply(score))
+    }
+
+    /// The class a detection of the model's `class_id` is reported as, or
+    /// `None` when it is suppressed or not one of [`DetectorConfig::classes`]
+    pub fn report_class(&self, class_id: usize) -> Option<usize> {
+        if self.suppressed_classes.contains(&class_id) {
+            return None;
+        }
+
+        let class_id = self
+            .class_aliases
+            .iter()
+            .find(|&&(from, _)| from == class_id)
+            .map_or(class_id, |&(_, to)| to);
+        self.classes.contains(&class_id).then_some(class_id)
+    }
+
+    /// Confidence threshold for a `width`x`height` image.
+    ///
+    /// Without [`DetectorConfig::adaptive_confidence`] this is simply
+    /// [`DetectorConfig::confidence`]. With it, the threshold is multiplied by
+    /// `1 + 0.1 * log2(1 / megapixels)`, clamped to 0.9-1.5 (and the result to
+    /// at most 1.0): a 1 MP image keeps the base threshold, each halving of
+    /// the pixel count raises it by 10%, and images of 2 MP or more get 10%
+    /// off.
+    pub fn confidence_for(&self, width: u32, height: u32) -> f32 {
+        if !self.adaptive_confidence || width == 0 || height == 0 {
+            return self.confidence;
+        }
+
+        let megapixels = (width as f32 * height as f32) / 1_000_000.0;
+        let factor = (1.0 - 0.1 * megapixels.log2()).clamp(0.9, 1.5);
+        (self.confidence * factor).min(1.0)
+    }
+}
+
+/// An image resized and normalized into the model's input layout (NCHW
+/// unless [`DetectorConfig::layout`] says otherwise).
+///
+/// Preparing inputs needs no session, so it can run on other threads while
+/// the detector is busy with inference; see [`CatDetector::detect_input`].
+#[derive(Clone, Debug)]
+pub struct ModelInput {
+    pub(crate) tensor: Array<f32, IxDyn>,
+    pub(crate) input_size: u32,
+    pub(crate) orig_width: u32,
+    pub(crate) orig_height: u32,
+    pub(crate) channel_order: ChannelOrder,
+    pub(crate) layout: TensorLayout,
+}
+
+impl ModelInput {
+    /// Resize `img` to `input_size` x `input_size` with the configured filter,
+    /// normalize its channels and lay them out in the configured order
+    pub fn new(img: &DynamicImage, input_size: u32, config: &DetectorConfig) -> Self {
+        let _span = span("preprocess", None);
+        let (orig_width, orig_height) = (img.width(), img.height());
+
+        // Resize to the model input size (640x640 for stock YOLOv8)
+        let img = img.resize_exact(input_size, input_size, config.resize_filter);
+        let img = img.to_rgb8();
+
+        // Convert to the model's layout and normalize
+        let layout = config.layout.unwrap_or_default();
+        let mut tensor = Array::zeros(IxDyn(&layout.shape(3, input_size as usize)));
+        let Normalization { mean, std } = config.normalization;
+        let order = config.channel_order;
+
+        for (x, y, pixel) in img.enumerate_pixels() {
+            for channel in 0..3 {
+                let c = order.color(channel);
+                tensor[layout.index(channel, y as usize, x as usize)] = (f32::from(pixel[c]) / 255.0 - mean[c]) / std[c];
+            }
+        }
+
+        Self {
+            tensor,
+            input_size,
+            orig_width,
+            orig_height,
+            channel_order: order,
+            layout,
+        }
+    }
+
+    pub fn input_size(&self) -> u32 {
+        self.input_size
+    }
+
+    /// The tensor as an RGB image, undoing `normalization` (which must be the
+    /// one the input was prepared with) and the channel order, to check what
+    /// the model sees
+    pub fn to_rgb_image(&self, normalization: &Normalization) -> RgbImage {
+        let Normalization { mean, std } = *normalization;
+
+        RgbImage::from_fn(self.input_size, self.input_size, |x, y| {
+            Rgb(std::array::from_fn(|c| {
+                // The color order is its own inverse
+                let channel = self.channel_order.color(c);
+                let value = self.tensor[self.layout.index(channel, y as usize, x as usize)] * std[c] + mean[c];
+                (value * 255.0).round().clamp(0.0, 255.0) as u8
+            }))
+        })
+    }
+}
+
+/// Runs the model on prepared inputs, locally ([`YoloCatDetector`]) or on an
+/// inference server ([`crate::RemoteDetector`]).
+///
+/// Implementors only turn a [`ModelInput`] into detections; loading,
+/// rotations and [`YoloCatDetector::scan`]-style walking are shared.
+pub trait CatDetector {
+    fn config(&self) -> &DetectorConfig;
+
+    /// Detections in `input`, mapped back to original image coordinates and
+    /// kept by the confidence and classes of `config` instead of the
+    /// detector's own (model-level settings such as tensor names always
+    /// come from the detector)
+    fn detect_input_with(&self, input: &ModelInput, config: &DetectorConfig) -> Result<Vec<Detection>>;
+
+    /// Run detection on an input prepared with [`ModelInput::new`]
+    fn detect_input(&self, input: &ModelInput) -> Result<Vec<Detection>> {
+        self.detect_input_with(input, self.config())
+    }
+
+    /// Load an image from disk and return its cat detections
+    fn detect(&self, image_path: &Path) -> Result<Vec<Detection>> {
+        let img = image::open(image_path).map_err(|e| DetectError::image(image_path, e))?;
+
+        self.detect_image(&img)
+    }
+
+    /// Run detection on an already-decoded image
+    fn detect_image(&self, img: &DynamicImage) -> Result<Vec<Detection>> {
+        self.detect_image_at(img, INPUT_SIZE)
+    }
+
+    /// Run detection with the image resized to `input_size` x `input_size`
+    /// instead of [`INPUT_SIZE`]; the model must accept dynamic input sizes
+    fn detect_image_at(&self, img: &DynamicImage, input_size: u32) -> Result<Vec<Detection>> {
+        self.detect_input(&ModelInput::new(img, input_size, self.config()))
+    }
+
+    /// Run detection on the image and its 90/180/270 degree rotations,
+    /// stopping at the first orientation that finds a cat.
+    ///
+    /// Returns the detections (mapped back to the unrotated image) and the
+    /// clockwise rotation in degrees that produced them.
+    fn detect_with_rotations(&self, img: &DynamicImage) -> Result<(Vec<Detection>, u32)> {
+        detect_rotations_from(self, img, (img.width(), img.height()), self.detect_image(img)?, self.config(), None)
+    }
+}
+
+/// [`CatDetector::detect_with_rotations`] given the detections of the
+/// upright image, decoding with `config`. Boxes are mapped to `original`, the
+/// size of the image in its file, which `img` may have been decoded below.
+/// Detections in `mask`ed zones are dropped from every attempt, so a masked
+/// hit doesn't stop the search for a cat at the next rotation.
+pub(crate) fn detect_rotations_from<D: CatDetector + ?Sized>(
+    detector: &D,
+    img: &DynamicImage,
+    original: (u32, u32),
+    upright: Vec<Detection>,
+    config: &DetectorConfig,
+    mask: Option<&IgnoreMask>,
+) -> Result<(Vec<Detection>, u32)> {
+    let (width, height) = original;
+    let mut upright = Some(upright);
+    let detect = |rotated: &DynamicImage, degrees: u32| {
+        let mut input = ModelInput::new(rotated, INPUT_SIZE, config);
+        (input.orig_width, input.orig_height) = if degrees == 180 { (width, height) } else { (height, width) };
+        detector.detect_input_with(&input, config)
+    };
+
+    for degrees in [0, 90, 180, 270] {
+        let detections = match degrees {
+            90 => detect(&img.rotate90(), 90)?,
+            180 => detect(&img.rotate180(), 180)?,
+            270 => detect(&img.rotate270(), 270)?,
+            _ => upright.take().unwrap_or_default(),
+        };
+
+        let mut detections: Vec<Detection> = detections
+            .into_iter()
+            .map(|d| Detection { bbox: d.bbox.unrotate(degrees, width, height), ..d })
+            .collect();
+        if let Some(mask) = mask {
+            mask.retain_unmasked(&mut detections, width, height);
+        }
+        if !detections.is_empty() {
+            return Ok((detections, degrees));
+        }
+    }
+
+    Ok((Vec::new(), 0))
+}
+
+/// Thresholds a model recommends in its ONNX metadata, under the custom keys
+/// `confidence` (or `conf`) and `iou` (or `iou_threshold`)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ModelThresholds {
+    pub confidence: Option<f32>,
+    pub iou_threshold: Option<f32>,
+}
+
+impl ModelThresholds {
+    fn read(session: &Session) -> Self {
+        let Ok(metadata) = session.metadata() else {
+            return Self::default();
+        };
+        let threshold = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|&key| metadata.custom(key).ok().flatten())
+                .and_then(|value| value.trim().parse::<f32>().ok())
+                .filter(|value| (0.0..=1.0).contains(value))
+        };
+
+        Self {
+            confidence: threshold(&["confidence", "conf"]),
+            iou_threshold: threshold(&["iou", "iou_threshold"]),
+        }
+    }
+}
+
+/// Whether a model's metadata says it takes raw 0-255 pixels (`Some(true)`)
+/// or pixels scaled to 0-1 (`Some(false)`), under the custom key
+/// `pixel_range` with the value `0-255` or `0-1`
+fn read_raw_pixels(session: &Session) -> Option<bool> {
+    let value = session.metadata().ok()?.custom("pixel_range").ok().flatten()?;
+    match value.trim() {
+        "0-255" | "255" => Some(true),
+        "0-1" | "1" => Some(false),
+        _ => None,
+    }
+}
+
+pub struct YoloCatDetector {
+    session: Session,
+    config: DetectorConfig,
+    // Position of DetectorConfig::output_name among the session outputs
+    output_index: usize,
+    model_thresholds: ModelThresholds,
+    raw_pixels: Option<bool>,
+    // Channels of the image input; beyond the first three they are zero-filled
+    input_channels: usize,
+}
+
+impl YoloCatDetector {
+    pub fn new(model_path: &Path, mut config: DetectorConfig) -> Result<Self> {
+        let session = load_session(model_path)?;
+        let model_thresholds = ModelThresholds::read(&session);
+        let raw_pixels = read_raw_pixels(&session);
+        let layout = resolve_layout(&session, &config);
+        config.layout = Some(layout);
+        let input_channels = match input_dimensions(&session).get(layout.channel_axis()) {
+            Some(&Some(channels)) => channels as usize,
+            _ => 3,
+        };
+        let mut detector = Self {
+            session,
+            config,
+            output_index: 0,
+            model_thresholds,
+            raw_pixels,
+            input_channels,
+        };
+
+        // Print model info for debugging
+        diag!("Model inputs: {:?}", detector.input_names());
+        diag!("Model outputs: {:?}", detector.output_names());
+
+        if let Some(name) = &detector.config.input_name {
+            tensor_index("input", name, &detector.input_names())?;
+            if detector.session.inputs.len() > 1 {
+                return Err(DetectError::InvalidInput {
+                    path: model_path.to_path_buf(),
+                    message: format!(
+                        "model has {} inputs; only the image input is supported",
+                        detector.session.inputs.len()
+                    ),
+                });
+            }
+        }
+        if let Some(name) = &detector.config.output_name {
+            detector.output_index = tensor_index("output", name, &detector.output_names())?;
+        }
+
+        if input_channels < 3 || (input_channels > 3 && !detector.config.zero_fill_channels) {
+            return Err(DetectError::InvalidInput {
+                path: model_path.to_path_buf(),
+                message: format!(
+                    "model takes {input_channels} input channels but images supply 3 (RGB){}",
+                    if input_channels > 3 { "; zero-fill the others with --zero-fill-channels" } else { "" }
+                ),
+            });
+        }
+        if input_channels > 3 {
+            diag!(
+                "Warning: model takes {input_channels} input channels; channels 4-{input_channels} are fed zeros, \
+                 so detections may be unreliable"
+            );
+        }
+
+        Ok(detector)
+    }
+
+    /// Channels of the model's image input: 3 for RGB models, more for
+    /// models that also take e.g. depth or stacked frames
+    pub fn input_channels(&self) -> usize {
+        self.input_channels
+    }
+
+    /// The tensor to feed the model for `input`, padded with zero channels
+    /// up to [`Self::input_channels`]
+    fn model_tensor<'a>(&self, input: &'a ModelInput) -> Cow<'a, Array<f32, IxDyn>> {
+        let axis = input.layout.channel_axis();
+        let mut shape = input.tensor.shape().to_vec();
+        if shape[axis] >= self.input_channels {
+            return Cow::Borrowed(&input.tensor);
+        }
+
+        let color_channels = shape[axis];
+        shape[axis] = self.input_channels;
+        let mut tensor = Array::zeros(IxDyn(&shape));
+        tensor
+            .slice_axis_mut(Axis(axis), Slice::from(0..color_channels))
+            .assign(&input.tensor);
+        Cow::Owned(tensor)
+    }
+
+    /// Names of the model's inputs, in the order ONNX Runtime takes them
+    pub fn input_names(&self) -> Vec<&str> {
+        self.session.inputs.iter().map(|input| input.name.as_str()).collect()
+    }
+
+    /// Names of the model's outputs, in the order ONNX Runtime returns them
+    pub fn output_names(&self) -> Vec<&str> {
+        self.session.outputs.iter().map(|output| output.name.as_str()).collect()
+    }
+
+    /// Thresholds recommended by the model's metadata, if any
+    pub fn model_thresholds(&self) -> ModelThresholds {
+        self.model_thresholds
+    }
+
+    /// Whether the model's metadata says it takes raw 0-255 pixels rather
+    /// than pixels scaled to 0-1; `None` when it does not say
+    pub fn model_expects_raw_pixels(&self) -> Option<bool> {
+        self.raw_pixels
+    }
+
+    /// The model's raw output for `input`, e.g. `[1, 84, 8400]` for a stock
+    /// YOLOv8 export, before any decoding or thresholds; with several
+    /// outputs, the one chosen by [`DetectorConfig::output_name`]
+    pub fn raw_output(&self, input: &ModelInput) -> Result<ArrayD<f32>> {
+        let outputs = run_model(&self.session, &self.model_tensor(input))?;
+        let output = outputs[self.output_index]
+            .try_extract::<f32>()
+            .map_err(DetectError::model("failed to extract output tensor"))?;
+        Ok(output.view().to_owned())
+    }
+
+    /// Replace the confidence and IoU thresholds of [`Self::config`], e.g.
+    /// with the [`Self::model_thresholds`]
+    pub fn set_thresholds(&mut self, confidence: f32, iou_threshold: f32) {
+        self.config.confidence = confidence;
+        self.config.iou_threshold = iou_threshold;
+    }
+
+    /// Replace the normalization of [`Self::config`], e.g. with
+    /// [`Normalization::RAW`] for a model that expects raw pixels
+    pub fn set_normalization(&mut self, normalization: Normalization) {
+        self.config.normalization = normalization;
+    }
+}
+
+impl CatDetector for YoloCatDetector {
+    fn config(&self) -> &DetectorConfig {
+        &self.config
+    }
+
+    fn detect_input_with(&self, input: &ModelInput, config: &DetectorConfig) -> Result<Vec<Detection>> {
+        let ModelInput {
+            input_size,
+            orig_width,
+            orig_height,
+            ..
+        } = input;
+        let (input_size, orig_width, orig_height) = (*input_size, *orig_width, *orig_height);
+
+        let outputs = run_model(&self.session, &self.model_tensor(input))?;
+        let _span = span("postprocess", None);
+        let output = outputs[self.output_index]
+            .try_extract::<f32>()
+            .map_err(DetectError::model("failed to extract output tensor"))?;
+
+        let output_view = output.view();
+        let shape = output_view.shape();
+
+        diag!("YOLOv8 output shape: {:?}", shape);
+
+        // Boxes are predicted in model input space; map them back to the original image
+        let scale = (
+            orig_width as f32 / input_size as f32,
+            orig_height as f32 / input_size as f32,
+        );
+
+        let threshold = config.confidence_for(orig_width, orig_height);
+
+        let detections = if shape.len() == 3 && shape[1] == 84 {
+            // Raw YOLOv8 head: [1, 84, 8400]
+            // Where 84 = 4 bbox coords + 80 class scores
+            // 8400 = number of predictions
+            let detections = decode_predictions(&output_view, scale, config, threshold);
+            non_max_suppression(detections, config.iou_threshold)
+        } else if is_end_to_end_shape(shape) {
+            // Exports with NMS in the graph: [num_dets, 6] or [1, num_dets, 6]
+            self.decode_end_to_end(&output_view, scale, config, threshold)
+        } else {
+            diag!("Unexpected output shape: {:?}", shape);
+            return Ok(Vec::new());
+        };
+        let detections = match config.merge_distance {
+            Some(distance) => merge_nearby(detections, distance),
+            None => detections,
+        };
+
+        if let Some(best) = detections.first() {
+            diag!(
+                "{} DETECTED! Confidence: {:.3}",
+                best.class_name().to_uppercase(),
+                best.confidence
+            );
+        }
+
+        Ok(detections)
+    }
+}
+
+/// Top `(class_id, score)` of each anchor of a raw `[84, N]` head. Each
+/// class is a contiguous row, so the rows are walked once keeping a running
+/// max rather than striding through all 80 rows for every anchor. NaN scores
+/// never win, and of tied scores the lowest class does.
+fn top_classes(predictions: ArrayView2<'_, f32>) -> Vec<(usize, f32)> {
+    let mut best = vec![(0, f32::NEG_INFINITY); predictions.shape()[1]];
+    for (class_id, row) in predictions.outer_iter().skip(4).take(80).enumerate() {
+        for (top, &score) in best.iter_mut().zip(row.iter()) {
+            if score > top.1 {
+                *top = (class_id, score);
+            }
+        }
+    }
+    best
+}
+
+/// Decode the raw `[1, 84, 8400]` YOLOv8 head: per anchor, the top class
+/// wins. Boxes are scaled by `(scale_x, scale_y)` from model input to image
+/// pixels, and only those of [`DetectorConfig::classes`] over `threshold`
+/// are kept, before non-maximum suppression. Panics when `output_view` is
+/// not three-dimensional.
+pub fn decode_predictions(
+    output_view: &ArrayViewD<'_, f32>,
+    (scale_x, scale_y): (f32, f32),
+    config: &DetectorConfig,
+    threshold: f32,
+) -> Vec<Detection> {
+    let predictions: ArrayView2<'_, f32> =
+        output_view.index_axis(Axis(0), 0).into_dimensionality().expect("raw head is [1, 84, N]");
+    let best = top_classes(predictions);
+
+    let mut detections: Vec<Detection> = Vec::new();
+    // Indices into detections of boxes that would survive NMS so far, for stop_at
+    let mut distinct: Vec<usize> = Vec::new();
+    // (anchor, class_id, score) candidates for the debug dump
+    let mut debug_candidates = Vec::new();
+
+    // Process each prediction
+    for (i, &(class_id, score)) in best.iter().enumerate() {
+        if config.debug_top_k > 0 && score > config.debug_min_conf {
+            debug_candidates.push((i, class_id, score));
+        }
+
+        // Check if it's a requested class with sufficient confidence
+        let confidence = config.calibrate(score);
+        let Some(reported) = config.report_class(class_id).filter(|_| confidence > threshold) else {
+            continue;
+        };
+
+        let cx = predictions[[0, i]];
+        let cy = predictions[[1, i]];
+        let w = predictions[[2, i]];
+        let h = predictions[[3, i]];
+        let detection = Detection {
+            class_id: reported,
+            confidence,
+            bbox: BoundingBox {
+                x1: (cx - w / 2.0) * scale_x,
+                y1: (cy - h / 2.0) * scale_y,
+                x2: (cx + w / 2.0) * scale_x,
+                y2: (cy + h / 2.0) * scale_y,
+            },
+        };
+
+        let is_distinct = config.stop_at.is_some()
+            && !distinct.iter().map(|&index| &detections[index]).any(|kept| {
+                kept.class_id == detection.class_id && kept.bbox.iou(&detection.bbox) > config.iou_threshold
+            });
+        if is_distinct {
+            distinct.push(detections.len());
+        }
+        detections.push(detection);
+
+        if config.stop_at.is_some_and(|stop_at| distinct.len() >= stop_at) {
+            break;
+        }
+    }
+
+    // Debug: show the highest-scoring anchors regardless of class
+    debug_candidates.sort_by(|a, b| b.2.total_cmp(&a.2));
+    for (i, class_id, score) in debug_candidates.into_iter().take(config.debug_top_k) {
+        diag!(
+            "Detection {}: class={} ({}), confidence={:.3}",
+            i, YOLO_CLASSES[class_id], class_id, score
+        );
+    }
+
+    detections
+}
+
+impl YoloCatDetector {
+    /// Decode post-NMS rows of `[x1, y1, x2, y2, score, class_id]`
+    fn decode_end_to_end(
+        &self,
+        output_view: &ArrayViewD<'_, f32>,
+        (scale_x, scale_y): (f32, f32),
+        config: &DetectorConfig,
+        threshold: f32,
+    ) -> Vec<Detection> {
+        let rows = if output_view.ndim() == 3 {
+            output_view.index_axis(Axis(0), 0)
+        } else {
+            output_view.view()
+        };
+
+        let mut detections: Vec<Detection> = rows
+            .outer_iter()
+            .filter(|row| row[[5]] >= 0.0)
+            .filter_map(|row| {
+                let confidence = config.calibrate(row[[4]]);
+                if confidence <= threshold {
+                    return None;
+                }
+                Some(Detection {
+                    class_id: config.report_class(row[[5]].round() as usize)?,
+                    confidence,
+                    bbox: BoundingBox {
+                        x1: row[[0]] * scale_x,
+                        y1: row[[1]] * scale_y,
+                        x2: row[[2]] * scale_x,
+                        y2: row[[3]] * scale_y,
+                    },
+                })
+            })
+            .collect();
+
+        detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        detections
+    }
+}
+
+/// Dimensions of the model's first input, `None` where dynamic
+pub(crate) fn input_dimensions(session: &Session) -> &[Option<u32>] {
+    session.inputs.first().map(|input| input.dimensions.as_slice()).unwrap_or_default()
+}
+
+/// The layout chosen in `config`, else the one the model's input shape
+/// shows, else NCHW. A chosen layout the shape contradicts is warned about.
+pub(crate) fn resolve_layout(session: &Session, config: &DetectorConfig) -> TensorLayout {
+    let detected = TensorLayout::detect(input_dimensions(session));
+
+    match (config.layout, detected) {
+        (Some(chosen), Some(detected)) if chosen != detected => {
+            diag!(
+                "Warning: the model input {:?} looks {detected:?}, but images are laid out {chosen:?}",
+                input_dimensions(session)
+            );
+            chosen
+        }
+        (Some(chosen), _) => chosen,
+        (None, Some(detected)) => {
+            if detected != TensorLayout::default() {
+                diag!("Model input is {detected:?}; laying images out to match");
+            }
+            detected
+        }
+        (None, None) => TensorLayout::default(),
+    }
+}
+
+/// Start an ONNX Runtime session for the model at `model_path`
+pub(crate) fn load_session(model_path: &Path) -> Result<Session> {
+    // Initialize ONNX Runtime environment
+    let environment = Arc::new(
+        Environment::builder()
+            .with_name("cat_detector")
+            .build()
+            .map_err(DetectError::model("failed to create ONNX Runtime environment"))?
+    );
+
+    // Load ONNX model
+    SessionBuilder::new(&environment)
+        .and_then(|builder| builder.with_model_from_file(model_path))
+        .map_err(DetectError::model(format!("failed to load ONNX model {}", model_path.display())))
+}
+
+/// Run `session` on one input tensor and return all of its outputs
+pub(crate) fn run_model(session: &Session, input_tensor: &Array<f32, IxDyn>) -> Result<Vec<Value<'static>>> {
+    let _span = span("infer", None);
+
+    // Create ORT tensor - the model only needs the image input
+    let shape = input_tensor.shape().to_vec();
+    let flattened: Vec<f32> = input_tensor.iter().copied().collect();
+    let cow_array = ndarray::CowArray::from(flattened.as_slice()).into_shape(shape).unwrap();
+
+    let input_tensor_values = Value::from_array(session.allocator(), &cow_array)
+        .map_err(DetectError::model("failed to create input tensor"))?;
+
+    session.run(vec![input_tensor_values]).map_err(|source| DetectError::Model {
+        context: format!("failed to run inference on input shape {:?}", input_tensor.shape()),
+        source,
+    })
+}
+
+/// Position of the tensor called `name` among `names`
+fn tensor_index(kind: &'static str, name: &str, names: &[&str]) -> Result<usize> {
+    names.iter().position(|&candidate| candidate == name).ok_or_else(|| DetectError::UnknownTensor {
+        kind,
+        name: name.to_string(),
+        available: names.iter().map(|&name| name.to_string()).collect(),
+    })
+}
+
+/// Greedy per-class non-maximum suppression.
+///
+/// YOLOv8 emits many overlapping anchors for the same object; this keeps the
+/// highest-scoring box and drops same-class boxes overlapping it by more than
+/// `iou_threshold`. The result is sorted by descending confidence.
+pub fn non_max_suppression(mut detections: Vec<Detection>, iou_threshold: f32) -> Vec<Detection> {
+    detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+    let mut kept: Vec<Detection> = Vec::with_capacity(detections.len());
+    for detection in detections {
+        let overlaps = kept.iter().any(|k| {
+            k.class_id == detection.class_id && k.bbox.iou(&detection.bbox) > iou_threshold
+        });
+        if !overlaps {
+            kept.push(detection);
+        }
+    }
+
+    kept
+}
+
+/// Greedy per-class merge of boxes by center distance, run after NMS.
+///
+/// IoU-based NMS leaves boxes that overlap little, so in dense scenes one
+/// cat can still come out as several near-adjacent boxes. This keeps the
+/// highest-scoring box and drops same-class boxes whose centers lie within
+/// `max_distance` pixels of it, so each cluster is counted once. The result
+/// is sorted by descending confidence.
+pub fn merge_nearby(mut detections: Vec<Detection>, max_distance: f32) -> Vec<Detection> {
+    detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+    let mut kept: Vec<Detection> = Vec::with_capacity(detections.len());
+    for detection in detections {
+        let (x, y) = detection.bbox.center();
+        let near = kept.iter().any(|k| {
+            let (kx, ky) = k.bbox.center();
+            k.class_id == detection.class_id && (x - kx).hypot(y - ky) <= max_distance
+        });
+        if !near {
+            kept.push(detection);
+        }
+    }
+
+    kept
+}
+
+/// Whether an output shape is the `[N, 6]` / `[1, N, 6]` layout of models exported with NMS
+fn is_end_to_end_shape(shape: &[usize]) -> bool {
+    matches!(shape, [_, 6] | [1, _, 6])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{Array2, s};
+
+    /// The argmax as it was before [`top_classes`]: all 80 class scores of
+    /// one anchor at a time
+    fn top_classes_per_anchor(predictions: ArrayView2<'_, f32>) -> Vec<(usize, f32)> {
+        (0..predictions.shape()[1])
+            .map(|anchor| {
+                let (mut class_id, mut score) = (0, f32::NEG_INFINITY);
+                for class in 0..80 {
+                    let class_score = predictions[[4 + class, anchor]];
+                    if class_score > score {
+                        (class_id, score) = (class, class_score);
+                    }
+                }
+                (class_id, score)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn row_wise_argmax_matches_per_anchor() {
+        let mut predictions = Array2::from_shape_fn((84, 64), |(row, anchor)| {
+            ((row * 7919 + anchor * 104_729) % 1000) as f32 / 1000.0
+        });
+        // Tied top scores, one in the first class
+        predictions[[4 + 3, 0]] = 2.0;
+        predictions[[4 + 40, 0]] = 2.0;
+        predictions[[4, 1]] = 2.0;
+        predictions[[4 + 79, 1]] = 2.0;
+        // NaN before, after and instead of the best score
+        predictions[[4, 2]] = f32::NAN;
+        predictions[[4 + 10, 2]] = 2.0;
+        predictions[[4 + 79, 3]] = f32::NAN;
+        predictions.column_mut(4).slice_mut(s![4..]).fill(f32::NAN);
+        // Scores below zero everywhere
+        predictions.column_mut(5).slice_mut(s![4..]).fill(-1.0);
+
+        let expected = top_classes_per_anchor(predictions.view());
+        let actual = top_classes(predictions.view());
+
+        // NaN != NaN, so compare the bits
+        let bits = |tops: &[(usize, f32)]| -> Vec<(usize, u32)> {
+            tops.iter().map(|&(class, score)| (class, score.to_bits())).collect()
+        };
+        assert_eq!(bits(&actual), bits(&expected));
+        assert_eq!(actual[0], (3, 2.0));
+        assert_eq!(actual[1], (0, 2.0));
+        assert_eq!(actual[2], (10, 2.0));
+        assert_eq!(actual[4], (0, f32::NEG_INFINITY));
+        assert_eq!(actual[5], (0, -1.0));
+    }
+}
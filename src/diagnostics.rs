@@ -0,0 +1,21 @@
+use std::sync::OnceLock;
+
+/// Receives each diagnostic line, without the trailing newline
+pub type DiagnosticSink = Box<dyn Fn(&str) + Send + Sync>;
+
+static SINK: OnceLock<DiagnosticSink> = OnceLock::new();
+
+/// Send every later diagnostic line (model details, skip explanations,
+/// warnings) to `sink` instead of stderr, e.g. to also keep them in a log
+/// file. Only the first sink set takes effect; returns whether this one did.
+pub fn set_diagnostic_sink(sink: DiagnosticSink) -> bool {
+    SINK.set(sink).is_ok()
+}
+
+/// Report one diagnostic line: to the sink if one is set, otherwise to stderr
+pub fn diagnostic(line: &str) {
+    match SINK.get() {
+        Some(sink) => sink(line),
+        None => eprintln!("{line}"),
+    }
+}
@@ -0,0 +1,184 @@
+use crate::detector::{DetectorConfig, class_id};
+use crate::error::{DetectError, Result};
+use crate::mask::IgnoreMask;
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// File name of per-directory settings
+pub const DIR_CONFIG_FILE: &str = ".catfinder.toml";
+
+/// Keys of one `.catfinder.toml`; every key is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DirConfigFile {
+    confidence: Option<f32>,
+    classes: Option<Vec<String>>,
+    /// Relative to the directory holding the file
+    mask: Option<PathBuf>,
+}
+
+/// Overrides in effect for one directory: every `.catfinder.toml` from the
+/// scan root down, with the nearest file winning for each key
+#[derive(Clone, Debug, Default)]
+pub struct DirSettings {
+    pub confidence: Option<f32>,
+    pub classes: Option<Vec<usize>>,
+    pub mask: Option<Arc<IgnoreMask>>,
+}
+
+/// Settings the caller chose explicitly, e.g. on the command line, which
+/// `.catfinder.toml` files leave alone
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PinnedSettings {
+    pub confidence: bool,
+    pub classes: bool,
+    pub mask: bool,
+}
+
+impl DirSettings {
+    /// Whether nothing is overridden
+    pub fn is_empty(&self) -> bool {
+        self.confidence.is_none() && self.classes.is_none() && self.mask.is_none()
+    }
+
+    /// Whether both override the same way, so results under one hold for
+    /// the other
+    pub fn same_as(&self, other: &Self) -> bool {
+        let same_mask = match (&self.mask, &other.mask) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        self.confidence == other.confidence && self.classes == other.classes && same_mask
+    }
+
+    /// `base` with the confidence and class overrides applied
+    pub fn apply<'a>(&self, base: &'a DetectorConfig) -> Cow<'a, DetectorConfig> {
+        if self.confidence.is_none() && self.classes.is_none() {
+            return Cow::Borrowed(base);
+        }
+
+        let mut config = base.clone();
+        if let Some(confidence) = self.confidence {
+            config.confidence = confidence;
+        }
+        if let Some(classes) = &self.classes {
+            config.classes = classes.clone();
+        }
+        Cow::Owned(config)
+    }
+
+    /// These settings overridden by the file in `dir`, except for `pinned` keys
+    fn merged(&self, dir: &Path, file: DirConfigFile, pinned: PinnedSettings) -> Result<Self, String> {
+        let mut settings = self.clone();
+
+        if let Some(confidence) = file.confidence {
+            if !(0.0..=1.0).contains(&confidence) {
+                return Err(format!("confidence {confidence} is not between 0 and 1"));
+            }
+            if !pinned.confidence {
+                settings.confidence = Some(confidence);
+            }
+        }
+        if let Some(names) = file.classes {
+            let classes = names
+                .iter()
+                .map(|name| class_id(name).ok_or_else(|| format!("unknown class '{name}'")))
+                .collect::<Result<Vec<_>, _>>()?;
+            if !pinned.classes {
+                settings.classes = Some(classes);
+            }
+        }
+        if let Some(mask) = file.mask
+            && !pinned.mask
+        {
+            let mask = IgnoreMask::load(&dir.join(mask)).map_err(|e| e.to_string())?;
+            settings.mask = Some(Arc::new(mask));
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Finds and caches the [`DirSettings`] of each directory a scan visits.
+///
+/// Files are looked up from an image's directory up to the scan root that
+/// contains it, never above, so a stray file elsewhere on the system cannot
+/// change results. An image outside every root only sees its own directory's
+/// file. Each directory's file is read once; a malformed file is reported on
+/// every image beneath it rather than aborting the scan.
+#[derive(Default)]
+pub struct DirConfigs {
+    // Canonical scan roots
+    roots: Vec<PathBuf>,
+    pinned: PinnedSettings,
+    // Canonical directory -> its merged settings, or the broken file and why
+    cache: HashMap<PathBuf, Result<Arc<DirSettings>, (PathBuf, String)>>,
+}
+
+impl DirConfigs {
+    /// Settings for images under the directories `roots`, leaving `pinned`
+    /// keys to the caller
+    pub fn new(roots: &[PathBuf], pinned: PinnedSettings) -> Self {
+        Self {
+            roots: roots.iter().map(|root| fs::canonicalize(root).unwrap_or_else(|_| root.clone())).collect(),
+            pinned,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Settings for the image at `path`
+    pub fn for_file(&mut self, path: &Path) -> Result<Arc<DirSettings>> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        // The innermost root holding the image, or its own directory
+        let top = self
+            .roots
+            .iter()
+            .filter(|root| dir.starts_with(root))
+            .max_by_key(|root| root.components().count())
+            .cloned()
+            .unwrap_or_else(|| dir.clone());
+
+        self.resolve(&dir, &top)
+            .map_err(|(path, message)| DetectError::InvalidInput { path, message })
+    }
+
+    fn resolve(&mut self, dir: &Path, top: &Path) -> Result<Arc<DirSettings>, (PathBuf, String)> {
+        if let Some(cached) = self.cache.get(dir) {
+            return cached.clone();
+        }
+
+        let parent = match dir.parent() {
+            Some(parent) if dir != top => self.resolve(parent, top),
+            _ => Ok(Arc::default()),
+        };
+        let pinned = self.pinned;
+        let resolved = parent.and_then(|parent| {
+            let file = dir.join(DIR_CONFIG_FILE);
+            match read_config(&file).map_err(|message| (file.clone(), message))? {
+                Some(config) => parent
+                    .merged(dir, config, pinned)
+                    .map(Arc::new)
+                    .map_err(|message| (file, message)),
+                None => Ok(parent),
+            }
+        });
+
+        self.cache.insert(dir.to_path_buf(), resolved.clone());
+        resolved
+    }
+}
+
+/// Parse `path`, or `None` when the directory has no config file
+fn read_config(path: &Path) -> Result<Option<DirConfigFile>, String> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    toml::from_str(&text).map(Some).map_err(|e| e.message().to_string())
+}
@@ -0,0 +1,174 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Errors returned by the `cat_finder` library.
+///
+/// Messages describe the failing operation; the underlying cause is exposed
+/// through [`std::error::Error::source`] so callers can print the full chain
+/// or inspect it.
+#[derive(Debug, Error)]
+pub enum DetectError {
+    /// Image data is corrupt, truncated or otherwise undecodable
+    #[error("failed to decode image {}", path.display())]
+    Decode {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+
+    /// The file is not in a format the image decoders support
+    #[error("unsupported image format: {}", path.display())]
+    UnsupportedFormat {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+
+    /// The image has more pixels than allowed (see [`crate::open_image`])
+    #[error("image {} is {width}x{height}, over the limit of {max_pixels} pixels", path.display())]
+    TooLarge {
+        path: PathBuf,
+        width: u32,
+        height: u32,
+        max_pixels: u64,
+    },
+
+    /// ONNX Runtime failed to load the model or run inference
+    #[error("{context}")]
+    Model {
+        context: String,
+        #[source]
+        source: ort::OrtError,
+    },
+
+    /// A tensor name given in [`crate::DetectorConfig`] is not in the model
+    #[error("model has no {kind} named '{name}' (available: {})", available.join(", "))]
+    UnknownTensor {
+        kind: &'static str,
+        name: String,
+        available: Vec<String>,
+    },
+
+    /// The inference server of a [`crate::RemoteDetector`] failed or sent
+    /// an unreadable response
+    #[error("{context}")]
+    Remote {
+        context: String,
+        #[source]
+        source: ureq::Error,
+    },
+
+    /// Reading and decoding an image did not finish within
+    /// [`crate::ScanOptions::io_timeout`]
+    #[error("timed out after {timeout:?} reading {}", path.display())]
+    Timeout { path: PathBuf, timeout: Duration },
+
+    /// Reading or writing a file failed
+    #[error("{context}: {}", path.display())]
+    Io {
+        context: &'static str,
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// Encoding an output image (crop or thumbnail) failed
+    #[error("failed to encode image {}", path.display())]
+    Encode {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+
+    /// The SQLite result store failed
+    #[error("{context}")]
+    Database {
+        context: &'static str,
+        #[source]
+        source: rusqlite::Error,
+    },
+
+    /// Watching a directory for new files failed
+    #[error("failed to watch {}", path.display())]
+    Watch {
+        path: PathBuf,
+        #[source]
+        source: notify::Error,
+    },
+
+    /// A worker thread panicked while handling an image, e.g. on a decoder bug
+    #[error("{stage} panicked on {}", path.display())]
+    Panicked { path: PathBuf, stage: &'static str },
+
+    /// An entry an [`crate::ImageSource`] listed is not a regular file (e.g.
+    /// a socket or FIFO)
+    #[error("not a regular file: {}", path.display())]
+    NotAFile { path: PathBuf },
+
+    /// An input file such as a manifest is malformed
+    #[error("invalid {}: {message}", path.display())]
+    InvalidInput { path: PathBuf, message: String },
+}
+
+/// Result type of the `cat_finder` library
+pub type Result<T, E = DetectError> = std::result::Result<T, E>;
+
+impl DetectError {
+    /// Classify an image-crate error for `path`
+    pub(crate) fn image(path: &Path, source: image::ImageError) -> Self {
+        let path = path.to_path_buf();
+        match source {
+            image::ImageError::IoError(source) => DetectError::Io {
+                context: "failed to read image",
+                path,
+                source,
+            },
+            image::ImageError::Unsupported(_) => DetectError::UnsupportedFormat { path, source },
+            source => DetectError::Decode { path, source },
+        }
+    }
+
+    pub(crate) fn io(context: &'static str, path: &Path) -> impl FnOnce(io::Error) -> Self {
+        move |source| DetectError::Io {
+            context,
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub(crate) fn model(context: impl Into<String>) -> impl FnOnce(ort::OrtError) -> Self {
+        let context = context.into();
+        move |source| DetectError::Model { context, source }
+    }
+
+    pub(crate) fn database(context: &'static str) -> impl FnOnce(rusqlite::Error) -> Self {
+        move |source| DetectError::Database { context, source }
+    }
+
+    /// Whether the image is in a recognized format but uses a codec or
+    /// feature the decoders lack (e.g. an unusual TIFF compression), rather
+    /// than being corrupt. The source error names the format and feature.
+    pub fn is_unsupported(&self) -> bool {
+        matches!(self, DetectError::UnsupportedFormat { .. })
+    }
+
+    /// Whether reading the image was abandoned for taking too long
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, DetectError::Timeout { .. })
+    }
+
+    /// Whether the image was rejected for its size, either by
+    /// `max_pixels` or by the decoder's own allocation limits
+    pub fn is_too_large(&self) -> bool {
+        matches!(
+            self,
+            DetectError::TooLarge { .. }
+                | DetectError::Decode {
+                    source: image::ImageError::Limits(_),
+                    ..
+                }
+        )
+    }
+}
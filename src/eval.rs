@@ -0,0 +1,133 @@
+use crate::error::{DetectError, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One labeled image from an evaluation manifest
+#[derive(Clone, Debug, Deserialize)]
+pub struct LabeledImage {
+    pub path: PathBuf,
+    pub has_cat: bool,
+}
+
+/// Read a ground-truth manifest.
+///
+/// CSV manifests have `path,has_cat` lines (an optional header row is
+/// skipped; labels accept `true/false`, `1/0`, `yes/no`). Files ending in
+/// `.json` hold an array of `{"path": ..., "has_cat": ...}` objects. Relative
+/// paths are resolved against the manifest's directory.
+pub fn read_manifest(manifest: &Path) -> Result<Vec<LabeledImage>> {
+    let contents =
+        fs::read_to_string(manifest).map_err(DetectError::io("failed to read manifest", manifest))?;
+
+    let entries = if manifest
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+    {
+        serde_json::from_str::<Vec<LabeledImage>>(&contents).map_err(|e| e.to_string())
+    } else {
+        parse_csv_manifest(&contents)
+    };
+    let mut entries = entries.map_err(|message| DetectError::InvalidInput {
+        path: manifest.to_path_buf(),
+        message,
+    })?;
+
+    let base = manifest.parent().unwrap_or(Path::new(""));
+    for entry in &mut entries {
+        if entry.path.is_relative() {
+            entry.path = base.join(&entry.path);
+        }
+    }
+
+    Ok(entries)
+}
+
+fn parse_csv_manifest(contents: &str) -> Result<Vec<LabeledImage>, String> {
+    let mut entries = Vec::new();
+    let mut first_row = true;
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let is_first_row = std::mem::replace(&mut first_row, false);
+
+        // Split on the last comma so paths containing commas still work
+        let (path, label) = line
+            .rsplit_once(',')
+            .ok_or_else(|| format!("line {}: expected path,has_cat", line_number + 1))?;
+
+        let has_cat = match parse_label(label) {
+            Some(has_cat) => has_cat,
+            // Header row
+            None if is_first_row => continue,
+            None => return Err(format!("line {}: invalid label '{}'", line_number + 1, label.trim())),
+        };
+
+        entries.push(LabeledImage {
+            path: PathBuf::from(path.trim()),
+            has_cat,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn parse_label(label: &str) -> Option<bool> {
+    match label.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "y" | "cat" => Some(true),
+        "0" | "false" | "no" | "n" | "notcat" => Some(false),
+        _ => None,
+    }
+}
+
+/// Binary confusion matrix of predicted vs. labeled cat presence
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConfusionMatrix {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub true_negatives: usize,
+    pub false_negatives: usize,
+}
+
+impl ConfusionMatrix {
+    pub fn record(&mut self, predicted: bool, actual: bool) {
+        match (predicted, actual) {
+            (true, true) => self.true_positives += 1,
+            (true, false) => self.false_positives += 1,
+            (false, false) => self.true_negatives += 1,
+            (false, true) => self.false_negatives += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.true_positives + self.false_positives + self.true_negatives + self.false_negatives
+    }
+
+    pub fn precision(&self) -> f64 {
+        ratio(self.true_positives, self.true_positives + self.false_positives)
+    }
+
+    pub fn recall(&self) -> f64 {
+        ratio(self.true_positives, self.true_positives + self.false_negatives)
+    }
+
+    pub fn f1(&self) -> f64 {
+        let (precision, recall) = (self.precision(), self.recall());
+        if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        }
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
@@ -0,0 +1,122 @@
+use crate::detector::Detection;
+use crate::error::{DetectError, Result};
+use image::{Rgb, RgbImage, imageops};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Grid cells along the longer edge of the frame
+pub const HEATMAP_CELLS: u32 = 64;
+
+/// Longest edge of the written heatmap image, in pixels
+const OUTPUT_SIZE: u32 = 1024;
+
+/// Where detections land across a set of same-sized images, such as the
+/// frames of a fixed camera.
+///
+/// Box centers are collected per image size; the heatmap is drawn from the
+/// size seen most often, since positions only line up between images of the
+/// same framing. Images of other sizes are reported by [`Heatmap::ignored`].
+#[derive(Debug, Default)]
+pub struct Heatmap {
+    frames: HashMap<(u32, u32), SizeGroup>,
+}
+
+/// Images of one size and their box centers, relative to that size
+#[derive(Debug, Default)]
+struct SizeGroup {
+    images: usize,
+    centers: Vec<(f32, f32)>,
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the box centers of one `width`x`height` image (which may have none)
+    pub fn add(&mut self, width: u32, height: u32, detections: &[Detection]) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let group = self.frames.entry((width, height)).or_default();
+        group.images += 1;
+        group.centers.extend(detections.iter().map(|d| {
+            let (x, y) = d.bbox.center();
+            (x / width as f32, y / height as f32)
+        }));
+    }
+
+    /// Size of the images the heatmap is drawn from
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.frames
+            .iter()
+            .max_by_key(|&(&(width, height), group)| (group.images, width, height))
+            .map(|(&dimensions, _)| dimensions)
+    }
+
+    /// Images left out because their size differs from [`Heatmap::dimensions`]
+    pub fn ignored(&self) -> usize {
+        let Some(dimensions) = self.dimensions() else {
+            return 0;
+        };
+
+        self.frames
+            .iter()
+            .filter(|&(&size, _)| size != dimensions)
+            .map(|(_, group)| group.images)
+            .sum()
+    }
+
+    /// Number of box centers counted into the heatmap
+    pub fn len(&self) -> usize {
+        self.dimensions()
+            .map_or(0, |dimensions| self.frames[&dimensions].centers.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Draw the grid with the frame's aspect ratio and write it to `path`
+    /// (format chosen by extension), hottest cells in white
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let (width, height) = self.dimensions().unwrap_or((1, 1));
+        let (cols, rows) = scale_to_fit(width, height, HEATMAP_CELLS);
+
+        let mut grid = vec![0u32; (cols * rows) as usize];
+        if let Some(group) = self.frames.get(&(width, height)) {
+            for &(x, y) in &group.centers {
+                let col = ((x * cols as f32) as u32).min(cols - 1);
+                let row = ((y * rows as f32) as u32).min(rows - 1);
+                grid[(row * cols + col) as usize] += 1;
+            }
+        }
+
+        let max = grid.iter().copied().max().unwrap_or(0).max(1);
+        let cells = RgbImage::from_fn(cols, rows, |col, row| {
+            heat_color(grid[(row * cols + col) as usize] as f32 / max as f32)
+        });
+
+        let (out_width, out_height) = scale_to_fit(width, height, OUTPUT_SIZE);
+        imageops::resize(&cells, out_width, out_height, imageops::FilterType::Nearest)
+            .save(path)
+            .map_err(|source| DetectError::Encode {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+}
+
+/// `width`x`height` scaled so the longer edge is `size` (each edge at least 1)
+fn scale_to_fit(width: u32, height: u32, size: u32) -> (u32, u32) {
+    let longest = width.max(height) as f32;
+    let scale = |edge: u32| ((edge as f32 / longest * size as f32).round() as u32).max(1);
+    (scale(width), scale(height))
+}
+
+/// Black through red and yellow to white as `heat` goes from 0 to 1
+fn heat_color(heat: f32) -> Rgb<u8> {
+    let channel = |start: f32| ((heat * 3.0 - start).clamp(0.0, 1.0) * 255.0) as u8;
+    Rgb([channel(0.0), channel(1.0), channel(2.0)])
+}
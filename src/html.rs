@@ -0,0 +1,155 @@
+use crate::detector::BoundingBox;
+use crate::error::{DetectError, Result};
+use crate::scan::ScanResult;
+use base64::Engine;
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+/// Longest edge of the embedded thumbnails, in pixels
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+const BOX_COLOR: Rgb<u8> = Rgb([0, 255, 0]);
+
+/// Self-contained HTML gallery of matched images.
+///
+/// Each entry embeds a base64 JPEG thumbnail with the detection boxes drawn
+/// on it and links to the original file, so the report can be opened
+/// anywhere without its images being copied alongside it.
+#[derive(Default)]
+pub struct HtmlReport {
+    entries: Vec<String>,
+}
+
+impl HtmlReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a matched image to the gallery
+    pub fn add(&mut self, result: &ScanResult) -> Result<()> {
+        let img = image::open(&result.path).map_err(|e| DetectError::image(&result.path, e))?;
+
+        let thumbnail = thumbnail_with_boxes(&img, result.detections.iter().map(|d| &d.bbox));
+
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(thumbnail)
+            .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+            .map_err(|source| DetectError::Encode {
+                path: result.path.clone(),
+                source,
+            })?;
+
+        let detections = result
+            .detections
+            .iter()
+            .map(|d| format!("{} {:.2}", d.class_name(), d.confidence))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let path = result.path.display().to_string();
+        self.entries.push(format!(
+            "<figure><a href=\"{href}\"><img src=\"data:image/jpeg;base64,{data}\" alt=\"{path}\"></a>\
+             <figcaption>{path}<br>{width}x{height} &middot; {detections}</figcaption></figure>",
+            href = escape_html(&file_url(&result.path)),
+            data = base64::engine::general_purpose::STANDARD.encode(&jpeg),
+            path = escape_html(&path),
+            width = img.width(),
+            height = img.height(),
+            detections = escape_html(&detections),
+        ));
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the gallery to `path`
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Cat Finder Report</title>\n\
+             <style>\nbody {{ font-family: sans-serif; margin: 1em; }}\n\
+             figure {{ display: inline-block; vertical-align: top; width: {size}px; margin: 0.5em; }}\n\
+             figcaption {{ font-size: 0.8em; word-break: break-all; }}\n</style>\n</head>\n<body>\n\
+             <h1>Cat Finder Report</h1>\n<p>{count} matched images</p>\n{entries}\n</body>\n</html>\n",
+            size = THUMBNAIL_SIZE,
+            count = self.entries.len(),
+            entries = self.entries.join("\n"),
+        );
+
+        fs::write(path, html).map_err(DetectError::io("failed to write HTML report", path))
+    }
+}
+
+/// Downscale to [`THUMBNAIL_SIZE`] and outline each box (given in original image coordinates)
+fn thumbnail_with_boxes<'a>(
+    img: &DynamicImage,
+    boxes: impl Iterator<Item = &'a BoundingBox>,
+) -> RgbImage {
+    let mut thumbnail = img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8();
+    let scale_x = thumbnail.width() as f32 / img.width() as f32;
+    let scale_y = thumbnail.height() as f32 / img.height() as f32;
+
+    for bbox in boxes {
+        draw_rect(
+            &mut thumbnail,
+            bbox.x1 * scale_x,
+            bbox.y1 * scale_y,
+            bbox.x2 * scale_x,
+            bbox.y2 * scale_y,
+        );
+    }
+
+    thumbnail
+}
+
+/// Draw a 2px rectangle outline, clipped to the image
+fn draw_rect(img: &mut RgbImage, x1: f32, y1: f32, x2: f32, y2: f32) {
+    let max_x = img.width().saturating_sub(1) as f32;
+    let max_y = img.height().saturating_sub(1) as f32;
+    let (x1, x2) = (x1.clamp(0.0, max_x) as u32, x2.clamp(0.0, max_x) as u32);
+    let (y1, y2) = (y1.clamp(0.0, max_y) as u32, y2.clamp(0.0, max_y) as u32);
+
+    for t in 0..2 {
+        for x in x1..=x2 {
+            img.put_pixel(x, (y1 + t).min(y2), BOX_COLOR);
+            img.put_pixel(x, y2.saturating_sub(t).max(y1), BOX_COLOR);
+        }
+        for y in y1..=y2 {
+            img.put_pixel((x1 + t).min(x2), y, BOX_COLOR);
+            img.put_pixel(x2.saturating_sub(t).max(x1), y, BOX_COLOR);
+        }
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// `file://` URL for a path, percent-encoding everything but unreserved characters and `/`
+fn file_url(path: &Path) -> String {
+    let absolute = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut url = String::from("file://");
+
+    for byte in absolute.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    url
+}
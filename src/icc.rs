@@ -0,0 +1,102 @@
+use crate::error::{DetectError, Result};
+use image::{ImageDecoder, ImageReader};
+use std::path::Path;
+
+/// The embedded ICC color profile of an image, as far as `--probe-color`
+/// needs it: which color space the pixels are in.
+///
+/// Decoders hand over pixel values as stored, so an image in a wide-gamut
+/// space such as Display P3 reaches the model with subtly shifted colors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColorProfile {
+    /// The profile's description tag, e.g. `Display P3` or `sRGB IEC61966-2.1`
+    pub description: Option<String>,
+    /// Data color space from the header, e.g. `RGB`, `GRAY` or `CMYK`
+    pub color_space: String,
+}
+
+impl ColorProfile {
+    /// Parse the header and description tag of an ICC profile
+    pub fn parse(icc: &[u8]) -> Option<Self> {
+        // The header is 128 bytes, followed by the tag count
+        if icc.len() < 132 || &icc[36..40] != b"acsp" {
+            return None;
+        }
+
+        Some(Self {
+            description: description(icc),
+            color_space: String::from_utf8_lossy(&icc[16..20]).trim().to_string(),
+        })
+    }
+
+    /// Whether the profile describes sRGB (or a grayscale space, which
+    /// converts to RGB without a gamut change)
+    pub fn is_srgb(&self) -> bool {
+        self.color_space == "GRAY"
+            || self
+                .description
+                .as_deref()
+                .is_some_and(|description| description.to_lowercase().contains("srgb"))
+    }
+
+    /// Name to show for the profile: its description, else its color space
+    pub fn name(&self) -> &str {
+        self.description.as_deref().unwrap_or(&self.color_space)
+    }
+}
+
+/// The ICC profile embedded in the image at `path`, if any. Only the header
+/// is read, not the pixels.
+pub fn read_color_profile(path: &Path) -> Result<Option<ColorProfile>> {
+    let icc = ImageReader::open(path)
+        .map_err(DetectError::io("failed to open image", path))?
+        .with_guessed_format()
+        .map_err(DetectError::io("failed to read image", path))?
+        .into_decoder()
+        .and_then(|mut decoder| decoder.icc_profile())
+        .map_err(|e| DetectError::image(path, e))?;
+
+    Ok(icc.as_deref().and_then(ColorProfile::parse))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().ok()?))
+}
+
+/// Text of the `desc` tag, in its ICC v2 (`desc`) or v4 (`mluc`) form
+fn description(icc: &[u8]) -> Option<String> {
+    let tag_count = read_u32(icc, 128)? as usize;
+    let entry = (0..tag_count.min(256))
+        .map(|index| 132 + index * 12)
+        .find(|&entry| icc.get(entry..entry + 4) == Some(&b"desc"[..]))?;
+    let offset = read_u32(icc, entry + 4)? as usize;
+    let size = read_u32(icc, entry + 8)? as usize;
+    let tag = icc.get(offset..offset.checked_add(size)?)?;
+
+    let text = match tag.get(0..4)? {
+        // ASCII count (including the terminating NUL) and text
+        b"desc" => {
+            let length = read_u32(tag, 8)? as usize;
+            let ascii = tag.get(12..12 + length)?;
+            String::from_utf8_lossy(ascii)
+                .trim_end_matches('\0')
+                .to_string()
+        }
+        // First of several localized UTF-16BE records
+        b"mluc" => {
+            let length = read_u32(tag, 20)? as usize;
+            let start = read_u32(tag, 24)? as usize;
+            let units: Vec<u16> = tag
+                .get(start..start + length)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
@@ -0,0 +1,88 @@
+//! Cat detection with YOLOv8 via ONNX Runtime.
+//!
+//! [`YoloCatDetector`] runs the model on a single image, and
+//! [`YoloCatDetector::scan`] walks a directory lazily, yielding one
+//! [`ScanResult`] per image. [`RemoteDetector`] offloads inference to an HTTP
+//! server; both implement [`CatDetector`]. The `cat-finder` binary is a thin
+//! consumer of this API.
+
+/// `eprintln!` through [`diagnostic`], so a sink set with
+/// [`set_diagnostic_sink`] sees the line too
+macro_rules! diag {
+    () => {
+        $crate::diagnostics::diagnostic("")
+    };
+    ($($arg:tt)*) => {
+        $crate::diagnostics::diagnostic(&format!($($arg)*))
+    };
+}
+
+mod burst;
+mod calibration;
+mod classifier;
+mod coco;
+mod contact;
+mod copytree;
+mod crop;
+mod db;
+mod decode;
+mod detector;
+mod diagnostics;
+mod dirconfig;
+mod error;
+mod eval;
+mod hash;
+mod heatmap;
+mod html;
+mod icc;
+mod mask;
+mod metadata;
+mod record;
+mod remote;
+mod scan;
+mod screenshot;
+mod sidecar;
+mod source;
+mod trace;
+mod watch;
+
+pub use burst::{BurstFinder, DEFAULT_BURST_DISTANCE};
+pub use calibration::Calibration;
+pub use classifier::{CLASSIFIER_INPUT_SIZE, CatClassifier};
+pub use coco::{COCO_CATEGORY_IDS, CocoDataset};
+pub use contact::{CONTACT_THUMBNAIL_SIZE, ContactSheet, DEFAULT_CONTACT_COLUMNS};
+pub use copytree::{CopyOutcome, TreeCopier};
+pub use crop::{CropFormat, CropStats, CropWriter, DEFAULT_CROP_QUALITY, crop_file_name, crop_rect, square_rect};
+pub use db::ResultDb;
+pub use decode::{
+    MAX_ANIMATION_FRAMES, exif_thumbnail, is_animated_format, is_jpeg, open_frames, open_image, open_jpeg_scaled,
+};
+pub use detector::{
+    ANIMAL_CLASS_IDS, BoundingBox, CAT_CLASS_ID, CatDetector, ChannelOrder, Detection, DetectorConfig, INPUT_SIZE, ModelInput,
+    ModelThresholds, Normalization, TensorLayout, YOLO_CLASSES, YoloCatDetector, available_execution_providers, class_id,
+    decode_predictions, execution_providers, merge_nearby, non_max_suppression, probe_execution_provider,
+};
+pub use diagnostics::{DiagnosticSink, diagnostic, set_diagnostic_sink};
+pub use dirconfig::{DIR_CONFIG_FILE, DirConfigs, DirSettings, PinnedSettings};
+pub use error::{DetectError, Result};
+pub use eval::{ConfusionMatrix, LabeledImage, read_manifest};
+pub use hash::{
+    DEFAULT_HASH_BUFFER_SIZE, calculate_sha256, calculate_sha256_with_buffer, contents_equal, dhash, hamming_distance,
+    read_hash_list,
+};
+pub use heatmap::{HEATMAP_CELLS, Heatmap};
+pub use html::HtmlReport;
+pub use icc::{ColorProfile, read_color_profile};
+pub use mask::IgnoreMask;
+pub use metadata::PhotoMetadata;
+pub use record::{DetectionRecord, ImageRecord, csv_field, read_records};
+pub use remote::RemoteDetector;
+pub use scan::{
+    FrameAggregation, ScaleResult, Scan, ScanOptions, ScanResult, SkipReason, SortOrder,
+    count_image_files, decode_for_scan, get_image_timestamp, is_image_file, modified_nanos,
+};
+pub use screenshot::{ScreenshotHint, screenshot_hint};
+pub use sidecar::{SidecarFormat, sidecar_path, write_sidecar};
+pub use source::{ImageSource, ListSource, Shard, WalkSource, is_hidden};
+pub use trace::{enable_tracing, trace_len, write_trace};
+pub use watch::FileWatcher;
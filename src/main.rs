@@ -1,13 +1,42 @@
 use anyhow::{Context, Result};
+use cat_finder::{
+    ANIMAL_CLASS_IDS, BurstFinder, Calibration, CatClassifier, CatDetector, ChannelOrder, CocoDataset,
+    ConfusionMatrix, ContactSheet, CopyOutcome, CropFormat, CropWriter, DEFAULT_BURST_DISTANCE,
+    DEFAULT_CONTACT_COLUMNS, DEFAULT_CROP_QUALITY, DetectError, Detection, DetectorConfig, FileWatcher,
+    FrameAggregation, Heatmap, HtmlReport, INPUT_SIZE, IgnoreMask, ImageRecord, ImageSource,
+    ModelInput, Normalization, PhotoMetadata, PinnedSettings, RemoteDetector, ResultDb, Scan, ScanOptions,
+    ScanResult, Shard, SidecarFormat, SkipReason, SortOrder, TensorLayout, TreeCopier, WalkSource, YOLO_CLASSES,
+    YoloCatDetector, available_execution_providers, calculate_sha256, calculate_sha256_with_buffer,
+    class_id, count_image_files, csv_field, decode_for_scan, enable_tracing, execution_providers, is_hidden,
+    modified_nanos, open_image, probe_execution_provider, read_hash_list, read_manifest,
+    read_records, trace_len, write_sidecar, write_trace,
+};
 use chrono::{DateTime, Local};
-use clap::Parser;
-use image::DynamicImage;
-use ndarray::{Array, IxDyn};
-use ort::{Environment, Session, SessionBuilder, Value};
-use std::fs;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
+use image::imageops::FilterType;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::ffi::OsString;
+use std::fmt::Write;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, LineWriter, Write as _};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use walkdir::WalkDir;
+use std::process::{Command, ExitCode, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// `eprintln!` that also reaches --log-file (see [`start_log`])
+macro_rules! diag {
+    () => {
+        cat_finder::diagnostic("")
+    };
+    ($($arg:tt)*) => {
+        cat_finder::diagnostic(&format!($($arg)*))
+    };
+}
 
 #[derive(Parser, Debug)]
 #[command(name = "cat-finder")]
@@ -21,262 +50,2835 @@ struct Args {
     #[arg(short, long)]
     verbose: bool,
 
+    /// Color terminal output: auto (when stderr is a terminal and NO_COLOR is unset), always or never
+    #[arg(long, value_enum, default_value = "auto", value_name = "WHEN")]
+    color: ColorChoice,
+
+    /// Same as --color never
+    #[arg(long)]
+    no_color: bool,
+
     /// Show timestamp (F for file-based, M for metadata-based)
     #[arg(short = 't', long)]
     timestamp: bool,
 
-    /// Confidence threshold for detection (0.0-1.0)
-    #[arg(long, default_value = "0.25")]
+    /// Timestamp format for --timestamp: iso, epoch, or a strftime pattern
+    #[arg(long, default_value = "%Y-%m-%d %H:%M:%S", value_parser = parse_timestamp_format)]
+    timestamp_format: TimestampFormat,
+
+    /// Confidence threshold for detection, 0.0-1.0 or a percentage such as 25% (default: the model's
+    /// recommendation, else 0.25)
+    #[arg(long, default_value = "0.25", value_parser = parse_confidence)]
     confidence: f32,
 
+    /// Raise the confidence threshold for small images and lower it slightly for large ones
+    #[arg(long)]
+    adaptive_confidence: bool,
+
+    /// List images whose best confidence falls in LOW-HIGH (e.g. 0.15-0.25) separately as "maybe", without a hit
+    #[arg(
+        long,
+        value_parser = parse_confidence_range,
+        value_name = "LOW-HIGH",
+        conflicts_with_all = ["adaptive_confidence", "eval", "compare_model"]
+    )]
+    maybe_range: Option<(f32, f32)>,
+
+    /// IoU threshold for merging overlapping boxes (default: the model's recommendation, else 0.45)
+    #[arg(long, default_value = "0.45")]
+    iou: f32,
+
+    /// Classes to detect, by COCO name or ID (repeatable or comma-separated)
+    #[arg(
+        long,
+        default_value = "cat",
+        value_delimiter = ',',
+        value_parser = parse_class,
+        value_name = "NAME"
+    )]
+    class: Vec<usize>,
+
+    /// Detect every COCO animal (bird, cat, dog, horse, sheep, cow, elephant, bear, zebra, giraffe), plus any --class
+    #[arg(long)]
+    any_animal: bool,
+
+    /// Report detections of one class as another, e.g. 77=cat to count teddy bears as cats (repeatable)
+    #[arg(long, value_parser = parse_alias, value_name = "FROM=TO")]
+    alias: Vec<(usize, usize)>,
+
+    /// Calibrate confidences with the transform in this TOML file (see docs/usage.md)
+    #[arg(long, value_name = "FILE")]
+    calibration: Option<PathBuf>,
+
+    /// Drop detections of these classes before --alias and thresholding (repeatable or comma-separated)
+    #[arg(long, value_delimiter = ',', value_parser = parse_class, value_name = "NAME")]
+    suppress: Vec<usize>,
+
+    /// Only report images with at least this many cats (or objects of the --class classes)
+    #[arg(long, default_value = "1", value_name = "N")]
+    min_cats: usize,
+
+    /// Count same-class boxes whose centers are within PX pixels of each other as one cat
+    #[arg(long, value_name = "PX", value_parser = parse_distance)]
+    merge_distance: Option<f32>,
+
+    /// Stop decoding an image's predictions once N distinct cats are found; a quick yes/no with 1
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "maybe_range")]
+    stop_at: Option<u32>,
+
     /// Path to YOLO ONNX model file
     #[arg(long, default_value = "models/yolov8n.onnx")]
     model: PathBuf,
+
+    /// Send preprocessed images to this HTTP inference server instead of loading --model
+    #[arg(long, value_name = "URL")]
+    remote_url: Option<String>,
+
+    /// Use this binary cat/not-cat classifier (ONNX) instead of --model: a faster yes/no without boxes
+    #[arg(long, value_name = "MODEL", conflicts_with_all = ["remote_url", "compare_model"])]
+    classifier: Option<PathBuf>,
+
+    /// Name of the model input to feed the image to (default: the first input)
+    #[arg(long, value_name = "NAME")]
+    input_name: Option<String>,
+
+    /// Name of the model output holding the predictions (default: the first output)
+    #[arg(long, value_name = "NAME")]
+    output_name: Option<String>,
+
+    /// Feed zeros to the extra input channels of models taking more than RGB (e.g. RGB plus depth)
+    #[arg(long)]
+    zero_fill_channels: bool,
+
+    /// Normalization applied after scaling pixels to 0-1
+    #[arg(long, value_enum, default_value = "none")]
+    normalize: Normalize,
+
+    /// Feed raw 0-255 pixel values, for models that scale their input themselves
+    #[arg(long, conflicts_with_all = ["normalize", "mean", "std"])]
+    no_normalize: bool,
+
+    /// Filter used to resize images to the model input size
+    #[arg(long, value_enum, default_value = "triangle")]
+    resize_filter: ResizeFilter,
+
+    /// Color channel order the model expects (--mean and --std are still given as r,g,b)
+    #[arg(long, value_enum, default_value = "rgb")]
+    channel_order: ChannelOrderArg,
+
+    /// Axis order of the model input (default: read from the model's input shape, else nchw)
+    #[arg(long, value_enum)]
+    layout: Option<LayoutArg>,
+
+    /// Per-channel mean for --normalize custom (r,g,b)
+    #[arg(long, value_parser = parse_rgb_triplet, value_name = "R,G,B")]
+    mean: Option<[f32; 3]>,
+
+    /// Per-channel standard deviation for --normalize custom (r,g,b)
+    #[arg(long, value_parser = parse_rgb_triplet, value_name = "R,G,B")]
+    std: Option<[f32; 3]>,
+
+    /// Print the N highest-scoring anchors of each image to stderr (0 disables)
+    #[arg(long, default_value = "0", value_name = "N")]
+    debug_top_k: usize,
+
+    /// Minimum score for anchors included in --debug-top-k output
+    #[arg(long, default_value = "0.3", value_name = "X")]
+    debug_min_conf: f32,
+
+    /// Output format for matched images
+    #[arg(long, value_enum, default_value = "text")]
+    format: OutputFormat,
+
+    /// With --format json or coco, indent the JSON over several lines for reading
+    #[arg(long)]
+    json_pretty: bool,
+
+    /// Include each matched image's SHA-256 in the output
+    #[arg(long)]
+    with_hash: bool,
+
+    /// With --format json (and JSON sidecars), include each matched image's camera, lens and GPS position from EXIF
+    #[arg(long)]
+    with_exif: bool,
+
+    /// Hash each file and reuse the result for byte-identical copies
+    #[arg(long)]
+    dedupe: bool,
+
+    /// Instead of matched images, print each directory containing a match once, sorted, after the scan
+    #[arg(long)]
+    match_dirs: bool,
+
+    /// Print each matched file once, even when symlinks make the scan reach it by several paths
+    #[arg(long)]
+    dedupe_output: bool,
+
+    /// Only analyze entries at least N levels below PATH, as in find -mindepth (2 skips the files directly in PATH)
+    #[arg(long, default_value = "0", value_name = "N", conflicts_with = "rescan")]
+    min_depth: usize,
+
+    /// Skip hidden files and directories (names starting with a dot), such as .thumbnails and .git (the default)
+    #[arg(long, overrides_with = "include_hidden")]
+    skip_hidden: bool,
+
+    /// Also walk hidden files and directories, which --skip-hidden leaves out by default
+    #[arg(long, overrides_with = "skip_hidden")]
+    include_hidden: bool,
+
+    /// Only analyze part I of N of the library (from 0), e.g. 0/4 to 3/4 on four machines
+    #[arg(long, value_name = "I/N", value_parser = parse_shard)]
+    shard: Option<Shard>,
+
+    /// Visit files in a deterministic order (siblings sorted within each directory)
+    #[arg(long, value_enum)]
+    sort: Option<SortArg>,
+
+    /// Run inference at each of these input sizes and merge the detections (model must accept dynamic sizes)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_parser = parse_input_size,
+        value_name = "SIZES",
+        conflicts_with = "try_rotations"
+    )]
+    multi_scale: Vec<u32>,
+
+    /// Also try 90/180/270 degree rotations when the upright image has no cat
+    #[arg(long)]
+    try_rotations: bool,
+
+    /// Also detect on a mirrored copy of each image and merge the boxes, for better recall at 2x inference cost
+    #[arg(long)]
+    tta: bool,
+
+    /// Save a crop of each detected cat into this directory
+    #[arg(long, value_name = "DIR")]
+    crop_dir: Option<PathBuf>,
+
+    /// Image format of crops written to --crop-dir
+    #[arg(long, value_enum, default_value = "jpg", requires = "crop_dir")]
+    output_format: CropFormatArg,
+
+    /// JPEG quality of crops from 1 to 100 [default: 75]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100), value_name = "N", requires = "crop_dir")]
+    output_quality: Option<u8>,
+
+    /// Rewrite crops that already exist in --crop-dir
+    #[arg(long, requires = "crop_dir")]
+    overwrite_crops: bool,
+
+    /// Expand each crop to a square around the cat, padding with black where it leaves the image
+    #[arg(long, requires = "crop_dir")]
+    crop_square: bool,
+
+    /// Copy matched images into DEST, keeping their path relative to PATH
+    #[arg(long, value_name = "DEST")]
+    copy_tree: Option<PathBuf>,
+
+    /// Run CMD on each matched image, with {} replaced by its path (e.g. "cp {} ~/cats/")
+    #[arg(long, value_name = "CMD")]
+    exec: Option<String>,
+
+    /// Pass many matched paths to each --exec invocation, in place of a {} argument
+    #[arg(long, requires = "exec")]
+    exec_batch: bool,
+
+    /// With --copy-tree, print where each image would be copied without copying
+    #[arg(long, requires = "copy_tree")]
+    dry_run: bool,
+
+    /// Ignore detections centered on black pixels of this mask image (stretched to each image)
+    #[arg(long, value_name = "IMAGE")]
+    mask: Option<PathBuf>,
+
+    /// Ignore .catfinder.toml files in the scanned directories
+    #[arg(long)]
+    no_dir_config: bool,
+
+    /// Read size in KiB when hashing files (--with-hash, --dedupe, --skip-hashes, --sqlite), up to 1048576
+    #[arg(long, default_value = "256", value_name = "KB", value_parser = clap::value_parser!(u32).range(1..=1_048_576))]
+    hash_buffer_kb: u32,
+
+    /// Decode and resize upcoming images on N dedicated threads while inference runs
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    preprocess_threads: Option<u16>,
+
+    /// Pause at least this long after each inference (e.g. 500ms or 2s; bare numbers are ms)
+    #[arg(long, value_name = "DURATION", value_parser = parse_interval)]
+    min_interval: Option<Duration>,
+
+    /// Stop the scan after N inference calls, e.g. to cap the cost of a paid --remote-url
+    #[arg(long, value_name = "N")]
+    max_inferences: Option<usize>,
+
+    /// Give up on reading, hashing or decoding a file after this long (e.g. 5s; bare numbers are ms)
+    #[arg(long, value_name = "DURATION", value_parser = parse_interval)]
+    io_timeout: Option<Duration>,
+
+    /// Analyze every frame of animated GIFs (up to 32) and flag the file when any frame,
+    /// a majority of frames, or the mean best confidence across frames passes
+    #[arg(long, value_enum, value_name = "MODE", default_value = "any")]
+    frame_agg: FrameAggArg,
+
+    /// Analyze only the first frame of animated GIFs (faster, may miss cats in later frames)
+    #[arg(long, conflicts_with = "frame_agg")]
+    first_frame_only: bool,
+
+    /// Skip images that look like screenshots or memes rather than photos (see docs/usage.md)
+    #[arg(long)]
+    skip_screenshots: bool,
+
+    /// Skip inference for files whose SHA-256 is listed in FILE (one per line), e.g. photos confirmed to show no cat
+    #[arg(long, value_name = "FILE", conflicts_with = "eval")]
+    skip_hashes: Option<PathBuf>,
+
+    /// Warn about images whose embedded ICC color profile is not sRGB (e.g. Display P3), whose
+    /// colors reach the model unconverted
+    #[arg(long)]
+    probe_color: bool,
+
+    /// Check each photo's embedded EXIF thumbnail first and only analyze the full image
+    /// when the thumbnail shows a possible cat
+    #[arg(long)]
+    thumbnail_prefilter: bool,
+
+    /// Confidence a detection on the EXIF thumbnail needs to send the full image to analysis
+    #[arg(
+        long,
+        default_value = "0.1",
+        value_name = "X",
+        value_parser = parse_confidence,
+        requires = "thumbnail_prefilter"
+    )]
+    prefilter_confidence: f32,
+
+    /// Print every file that is passed over, with the reason
+    #[arg(long)]
+    explain_skips: bool,
+
+    /// Skip images with more than this many pixels instead of decoding them
+    #[arg(long, value_name = "N")]
+    max_pixels: Option<u64>,
+
+    /// Decode large JPEGs at a reduced DCT scale that still covers the model input (faster)
+    #[arg(long)]
+    jpeg_dct_scaling: bool,
+
+    /// Write a self-contained HTML gallery of matched images to this file
+    #[arg(long, value_name = "FILE")]
+    html_report: Option<PathBuf>,
+
+    /// Tile the best crop of every match into one review image (split into numbered sheets when large)
+    #[arg(long, value_name = "FILE")]
+    contact_sheet: Option<PathBuf>,
+
+    /// Thumbnails per row in --contact-sheet
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_CONTACT_COLUMNS, value_parser = clap::value_parser!(u32).range(1..), requires = "contact_sheet")]
+    contact_sheet_columns: u32,
+
+    /// Write a heatmap image of where detections appear across same-sized images
+    #[arg(long, value_name = "FILE")]
+    heatmap: Option<PathBuf>,
+
+    /// After the scan, list groups of matched photos showing the same cat in the same pose
+    #[arg(long)]
+    bursts: bool,
+
+    /// Most differing bits between crop hashes of photos in one --bursts group
+    #[arg(long, default_value_t = DEFAULT_BURST_DISTANCE, value_name = "BITS", requires = "bursts")]
+    burst_distance: u32,
+
+    /// Abort with a nonzero exit status once more than N images have failed
+    #[arg(long, value_name = "N")]
+    max_errors: Option<usize>,
+
+    /// Periodically overwrite FILE with JSON progress for monitoring headless runs
+    #[arg(long, value_name = "FILE")]
+    heartbeat: Option<PathBuf>,
+
+    /// Seconds between --heartbeat writes
+    #[arg(long, default_value = "10", value_name = "SECS", requires = "heartbeat")]
+    heartbeat_interval: u64,
+
+    /// Write the scan's counts (files encountered, skipped per reason, processed, hits, errors) to FILE as JSON
+    #[arg(long, value_name = "FILE")]
+    stats: Option<PathBuf>,
+
+    /// Record timing spans (walk, decode, preprocess, infer, postprocess) and write them to FILE as Chrome trace JSON
+    #[arg(long, value_name = "FILE", conflicts_with = "watch")]
+    trace: Option<PathBuf>,
+
+    /// Write each matched image's detections to a sidecar file next to it
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    sidecar: Option<SidecarArg>,
+
+    /// With --sidecar, also write sidecars for images without a match
+    #[arg(long, requires = "sidecar")]
+    sidecar_all: bool,
+
+    /// Record every analyzed image's detections in this SQLite database
+    #[arg(long, value_name = "DB")]
+    sqlite: Option<PathBuf>,
+
+    /// Skip images already in --sqlite with the same modification time and detection settings
+    #[arg(long, requires = "sqlite")]
+    skip_known: bool,
+
+    /// Also write the paths of matched images to this file
+    #[arg(long, value_name = "FILE")]
+    found_list: Option<PathBuf>,
+
+    /// Write the paths of images analyzed without a match to this file
+    #[arg(long, value_name = "FILE")]
+    not_found_list: Option<PathBuf>,
+
+    /// Write path,count,max_conf,width,height for every analyzed image to this CSV file
+    #[arg(long, value_name = "FILE")]
+    analysis_csv: Option<PathBuf>,
+
+    /// After the scan, list the files whose format was recognized but whose codec cannot be decoded
+    #[arg(long)]
+    report_unsupported: bool,
+
+    /// After the scan, list the N largest matched images by file size
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    report_largest: Option<u32>,
+
+    /// Rank --report-largest by pixel count instead of file size
+    #[arg(long, requires = "report_largest")]
+    by_pixels: bool,
+
+    /// Also write every diagnostic line (model details, verbose output, errors) to this file
+    #[arg(long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// How --log-file treats the log of a previous run
+    #[arg(long, value_enum, default_value = "truncate", value_name = "MODE", requires = "log_file")]
+    log_mode: LogMode,
+
+    /// Write diagnostics only to --log-file, not to stderr
+    #[arg(long, requires = "log_file")]
+    log_quiet: bool,
+
+    /// Write the paths of images that could not be analyzed to this file
+    #[arg(long, value_name = "FILE")]
+    error_list: Option<PathBuf>,
+
+    /// Only analyze the paths listed in FILE (e.g. a previous --error-list) instead of scanning PATH
+    #[arg(long, value_name = "FILE", conflicts_with = "path")]
+    rescan: Option<PathBuf>,
+
+    /// Keep watching PATH and analyze each image created or changed below it, printing hits as they appear
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "rescan", "estimate", "eval", "compare_model", "dedupe", "match_dirs", "maybe_range", "max_inferences",
+            // Outputs of a full scan, which watching never writes
+            "crop_dir", "sidecar", "sqlite", "html_report", "copy_tree", "found_list", "not_found_list", "error_list",
+            "contact_sheet", "heatmap", "heartbeat", "stats", "analysis_csv", "bursts", "report_unsupported",
+            "report_largest",
+        ]
+    )]
+    watch: bool,
+
+    /// With --watch, how long a new file must go without changes before it is analyzed (e.g. 500ms, 2s)
+    #[arg(long, default_value = "1s", value_parser = parse_interval, value_name = "DURATION", requires = "watch")]
+    watch_settle: Duration,
+
+    /// Terminate printed paths and list entries with NUL instead of newline
+    #[arg(long)]
+    print0: bool,
+
+    /// Print version, ONNX Runtime and model details for bug reports, and exit
+    #[arg(long)]
+    diagnostics: bool,
+
+    /// List the ONNX Runtime execution providers and whether a session on each starts with --model, and exit
+    #[arg(long)]
+    list_providers: bool,
+
+    /// Time inference on a few images and print the projected scan time instead of scanning
+    #[arg(long)]
+    estimate: bool,
+
+    /// Write the model input prepared from the image at PATH as a PNG, and exit
+    #[arg(long, value_name = "OUT")]
+    dump_preprocessed: Option<PathBuf>,
+
+    /// Run the model on the image at PATH and write its raw output tensor to OUT as a NumPy .npy file, and exit
+    #[arg(long, value_name = "OUT", conflicts_with_all = ["remote_url", "classifier", "dump_preprocessed"])]
+    dump_output: Option<PathBuf>,
+
+    /// Print the class names the model can detect, with their IDs, and exit
+    #[arg(long)]
+    print_classes: bool,
+
+    /// Run a second model on every image and report where the two disagree
+    #[arg(long, value_name = "PATH")]
+    compare_model: Option<PathBuf>,
+
+    /// Combine --format json outputs of --with-hash runs into one, keeping one entry per SHA-256, and exit
+    #[arg(long, value_name = "FILE", num_args = 1.., conflicts_with = "path")]
+    merge: Vec<PathBuf>,
+
+    /// Evaluate against a labeled manifest (CSV of path,has_cat or JSON) instead of scanning
+    #[arg(long, value_name = "MANIFEST")]
+    eval: Option<PathBuf>,
 }
 
-// YOLO COCO class names (for reference, not used in simplified detection)
-#[allow(dead_code)]
-const YOLO_CLASSES: [&str; 80] = [
-    "person", "bicycle", "car", "motorcycle", "airplane", "bus", "train", "truck", "boat",
-    "traffic light", "fire hydrant", "stop sign", "parking meter", "bench", "bird", "cat",
-    "dog", "horse", "sheep", "cow", "elephant", "bear", "zebra", "giraffe", "backpack",
-    "umbrella", "handbag", "tie", "suitcase", "frisbee", "skis", "snowboard", "sports ball",
-    "kite", "baseball bat", "baseball glove", "skateboard", "surfboard", "tennis racket",
-    "bottle", "wine glass", "cup", "fork", "knife", "spoon", "bowl", "banana", "apple",
-    "sandwich", "orange", "broccoli", "carrot", "hot dog", "pizza", "donut", "cake", "chair",
-    "couch", "potted plant", "bed", "dining table", "toilet", "tv", "laptop", "mouse", "remote",
-    "keyboard", "cell phone", "microwave", "oven", "toaster", "sink", "refrigerator", "book",
-    "clock", "vase", "scissors", "teddy bear", "hair drier", "toothbrush"
-];
-
-const CAT_CLASS_ID: usize = 15;  // Index of "cat" in YOLO classes
-
-struct YoloCatDetector {
-    session: Session,
-    confidence_threshold: f32,
-}
-
-impl YoloCatDetector {
-    fn new(model_path: &Path, confidence: f32) -> Result<Self> {
-        // Initialize ONNX Runtime environment
-        let environment = Arc::new(
-            Environment::builder()
-                .with_name("cat_detector")
-                .build()
-                .context("Failed to create ONNX Runtime environment")?
-        );
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+const RED: &str = "31";
 
-        // Load ONNX model
-        let session = SessionBuilder::new(&environment)?
-            .with_model_from_file(model_path)
-            .context("Failed to load ONNX model")?;
+/// Route diagnostics to `path`, one timestamped line each without color
+/// codes, and unless `quiet` to stderr as before
+fn start_log(path: &Path, mode: LogMode, quiet: bool) -> Result<()> {
+    if mode == LogMode::Rotate && path.exists() {
+        let mut rotated = path.as_os_str().to_owned();
+        rotated.push(".1");
+        fs::rename(path, &rotated).with_context(|| format!("Failed to rotate log file: {}", path.display()))?;
+    }
 
-        // Print model info for debugging
-        eprintln!("Model inputs: {:?}", session.inputs.iter().map(|i| &i.name).collect::<Vec<_>>());
-        eprintln!("Model outputs: {:?}", session.outputs.iter().map(|o| &o.name).collect::<Vec<_>>());
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(mode == LogMode::Append)
+        .truncate(mode != LogMode::Append)
+        .open(path)
+        .with_context(|| format!("Failed to open log file: {}", path.display()))?;
+    // Line-buffered, so the log is complete up to the last line even after a crash
+    let file = Mutex::new(LineWriter::new(file));
 
-        Ok(Self {
-            session,
-            confidence_threshold: confidence,
-        })
+    cat_finder::set_diagnostic_sink(Box::new(move |line| {
+        if !quiet {
+            eprintln!("{line}");
+        }
+        let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+        // A full disk should not end the scan
+        let _ = writeln!(file, "{} {}", Local::now().format("%Y-%m-%d %H:%M:%S"), strip_color(line));
+    }));
+    Ok(())
+}
+
+/// `text` without the escape codes added by [`paint`]
+fn strip_color(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('\x1b') {
+        plain.push_str(&rest[..start]);
+        rest = rest[start..].split_once('m').map_or("", |(_, after)| after);
+    }
+    plain.push_str(rest);
+    plain
+}
+
+/// Whether human-readable (stderr) output should use ANSI colors
+fn use_color(args: &Args) -> bool {
+    match (args.no_color, args.color) {
+        (true, _) | (_, ColorChoice::Never) => false,
+        (_, ColorChoice::Always) => true,
+        (_, ColorChoice::Auto) => {
+            std::io::stderr().is_terminal()
+                && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        }
+    }
+}
+
+/// Wrap `text` in an ANSI color when enabled
+fn paint(text: &str, color: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{color}m{text}\x1b[0m")
+    } else {
+        text.to_string()
     }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Normalize {
+    /// Plain /255 scaling (Ultralytics default)
+    None,
+    /// ImageNet mean/std after /255 scaling
+    Imagenet,
+    /// Mean/std given by --mean and --std
+    Custom,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum CropFormatArg {
+    /// Lossy JPEG, sized by --output-quality
+    Jpg,
+    /// Lossless PNG
+    Png,
+    /// Lossless WebP
+    Webp,
+}
 
-    fn detect_cats(&self, image_path: &Path) -> Result<bool> {
-        // Load and preprocess image
-        let img = image::open(image_path)
-            .with_context(|| format!("Failed to open image: {}", image_path.display()))?;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ChannelOrderArg {
+    /// Red, green, blue (Ultralytics default)
+    Rgb,
+    /// Blue, green, red, for models trained on OpenCV-loaded images
+    Bgr,
+}
 
-        let input_tensor = self.preprocess_image(img);
+impl From<ChannelOrderArg> for ChannelOrder {
+    fn from(order: ChannelOrderArg) -> Self {
+        match order {
+            ChannelOrderArg::Rgb => ChannelOrder::Rgb,
+            ChannelOrderArg::Bgr => ChannelOrder::Bgr,
+        }
+    }
+}
 
-        // Create ORT tensor - YOLOv8 only needs the image input
-        let shape = input_tensor.shape().to_vec();
-        let flattened: Vec<f32> = input_tensor.iter().copied().collect();
-        let cow_array = ndarray::CowArray::from(flattened.as_slice()).into_shape(shape).unwrap();
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LayoutArg {
+    /// Batch, channels, height, width (PyTorch, Ultralytics)
+    Nchw,
+    /// Batch, height, width, channels (TensorFlow, Keras)
+    Nhwc,
+}
 
-        let input_tensor_values = Value::from_array(
-            self.session.allocator(),
-            &cow_array
-        ).context("Failed to create input tensor")?;
+impl From<LayoutArg> for TensorLayout {
+    fn from(layout: LayoutArg) -> Self {
+        match layout {
+            LayoutArg::Nchw => TensorLayout::Nchw,
+            LayoutArg::Nhwc => TensorLayout::Nhwc,
+        }
+    }
+}
 
-        // Run inference - YOLOv8 takes single input
-        let outputs = self.session
-            .run(vec![input_tensor_values])
-            .with_context(|| {
-                format!("Failed to run inference. Input shape: {:?}", input_tensor.shape())
-            })?;
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum ResizeFilter {
+    /// Nearest neighbour: fastest, blockiest
+    Nearest,
+    /// Bilinear, as used by Ultralytics
+    Triangle,
+    /// Bicubic Catmull-Rom
+    #[value(name = "catmullrom")]
+    CatmullRom,
+    /// Lanczos with a window of 3: sharpest, slowest
+    Lanczos3,
+}
 
-        // YOLOv8 output format: [1, 84, 8400]
-        // Where 84 = 4 bbox coords + 80 class scores
-        // 8400 = number of predictions
+impl From<ResizeFilter> for FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
 
-        let output = outputs[0]
-            .try_extract::<f32>()
-            .context("Failed to extract output tensor")?;
+/// How --log-file starts each run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum LogMode {
+    /// Replace the previous run's log
+    Truncate,
+    /// Add to the end of the existing log
+    Append,
+    /// Keep the previous log as FILE.1, replacing an older one, and start afresh
+    Rotate,
+}
 
-        let output_view = output.view();
-        let shape = output_view.shape();
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SortArg {
+    /// File name
+    Name,
+    /// Modification time, oldest first
+    Mtime,
+    /// File size, smallest first
+    Size,
+}
 
-        eprintln!("YOLOv8 output shape: {:?}", shape);
+impl From<SortArg> for SortOrder {
+    fn from(sort: SortArg) -> Self {
+        match sort {
+            SortArg::Name => SortOrder::Name,
+            SortArg::Mtime => SortOrder::Mtime,
+            SortArg::Size => SortOrder::Size,
+        }
+    }
+}
 
-        // Expected shape: [1, 84, 8400]
-        if shape.len() == 3 && shape[1] == 84 {
-            let num_predictions = shape[2];
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// One path per line, with optional [..] annotations
+    Text,
+    /// One JSON object per line with path and detections
+    Json,
+    /// One COCO detection JSON document for the whole scan, printed when it ends
+    Coco,
+}
 
-            // Process each prediction
-            for i in 0..num_predictions {
-                // Get the prediction data for this anchor
-                let mut class_scores = vec![];
-                for class_id in 0..80 {
-                    class_scores.push((class_id, output_view[[0, 4 + class_id, i]]));
-                }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum FrameAggArg {
+    /// Flag when any frame has a cat
+    Any,
+    /// Flag when more than half of the frames have a cat
+    Majority,
+    /// Flag when the mean best-cat confidence across frames reaches --confidence
+    Mean,
+}
 
-                // Find the class with highest score
-                if let Some((class_id, score)) = class_scores.iter()
-                    .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
-                {
-                    // Check if it's a cat with sufficient confidence
-                    if *class_id == CAT_CLASS_ID && *score > self.confidence_threshold {
-                        eprintln!("CAT DETECTED! Confidence: {:.3}", score);
-                        return Ok(true);
-                    }
+impl From<FrameAggArg> for FrameAggregation {
+    fn from(arg: FrameAggArg) -> Self {
+        match arg {
+            FrameAggArg::Any => FrameAggregation::Any,
+            FrameAggArg::Majority => FrameAggregation::Majority,
+            FrameAggArg::Mean => FrameAggregation::Mean,
+        }
+    }
+}
 
-                    // Debug: show high confidence detections
-                    if *score > 0.3 && i < 10 {
-                        eprintln!("Detection {}: class_id={}, confidence={:.3}", i, class_id, score);
-                    }
-                }
-            }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SidecarArg {
+    /// `<image>.json` with the same fields as --format json
+    Json,
+    /// `<image>.txt` with `class_id confidence x1 y1 x2 y2` per detection
+    Txt,
+}
 
-            Ok(false)
-        } else {
-            eprintln!("Unexpected output shape: {:?}", shape);
-            Ok(false)
+impl From<SidecarArg> for SidecarFormat {
+    fn from(arg: SidecarArg) -> Self {
+        match arg {
+            SidecarArg::Json => SidecarFormat::Json,
+            SidecarArg::Txt => SidecarFormat::Txt,
         }
     }
+}
 
-    fn preprocess_image(&self, img: DynamicImage) -> Array<f32, IxDyn> {
-        // Resize to 640x640 (YOLOv8 input size)
-        let img = img.resize_exact(640, 640, image::imageops::FilterType::Triangle);
-        let img = img.to_rgb8();
+#[derive(Clone, Debug)]
+enum TimestampFormat {
+    /// RFC 3339 / ISO 8601 with UTC offset
+    Iso,
+    /// Seconds since the Unix epoch
+    Epoch,
+    Strftime(String),
+}
 
-        // Convert to NCHW format and normalize
-        let mut input = Array::zeros(IxDyn(&[1, 3, 640, 640]));
+impl TimestampFormat {
+    fn format(&self, timestamp: &DateTime<Local>) -> String {
+        match self {
+            Self::Iso => timestamp.to_rfc3339(),
+            Self::Epoch => timestamp.timestamp().to_string(),
+            Self::Strftime(pattern) => timestamp.format(pattern).to_string(),
+        }
+    }
+}
 
-        for (x, y, pixel) in img.enumerate_pixels() {
-            input[[0, 0, y as usize, x as usize]] = f32::from(pixel[0]) / 255.0;
-            input[[0, 1, y as usize, x as usize]] = f32::from(pixel[1]) / 255.0;
-            input[[0, 2, y as usize, x as usize]] = f32::from(pixel[2]) / 255.0;
+fn parse_timestamp_format(s: &str) -> Result<TimestampFormat, String> {
+    match s {
+        "iso" => Ok(TimestampFormat::Iso),
+        "epoch" => Ok(TimestampFormat::Epoch),
+        pattern => {
+            // Format a sample up front so a bad pattern fails here rather than mid-scan
+            let mut sample = String::new();
+            write!(sample, "{}", Local::now().format(pattern))
+                .map_err(|_| format!("invalid strftime pattern '{pattern}'"))?;
+            Ok(TimestampFormat::Strftime(pattern.to_string()))
         }
+    }
+}
+
+fn normalization_from_args(args: &Args) -> Result<Normalization> {
+    if args.no_normalize {
+        return Ok(Normalization::RAW);
+    }
+    if args.normalize != Normalize::Custom && (args.mean.is_some() || args.std.is_some()) {
+        anyhow::bail!("--mean and --std are only used with --normalize custom");
+    }
 
-        input
+    let normalization = match args.normalize {
+        Normalize::None => Normalization::default(),
+        Normalize::Imagenet => Normalization::IMAGENET,
+        Normalize::Custom => Normalization {
+            mean: args.mean.context("--normalize custom requires --mean r,g,b")?,
+            std: args.std.context("--normalize custom requires --std r,g,b")?,
+        },
+    };
+
+    if normalization.std.contains(&0.0) {
+        anyhow::bail!("--std values must be non-zero");
     }
+
+    Ok(normalization)
 }
 
-fn get_image_timestamp(path: &Path) -> Option<(DateTime<Local>, char)> {
-    // Get file modification time
-    fs::metadata(path)
-        .ok()
-        .and_then(|metadata| metadata.modified().ok())
-        .map(|modified| (DateTime::from(modified), 'F'))
+/// Feed raw pixels when the model's metadata asks for them and no
+/// normalization was chosen on the command line; warn when a chosen one
+/// contradicts the metadata
+fn apply_model_pixel_range(detector: &mut YoloCatDetector, config: &mut DetectorConfig, chosen: bool) {
+    let Some(raw) = detector.model_expects_raw_pixels() else {
+        return;
+    };
+    let feeding_raw = config.normalization == Normalization::RAW;
+
+    if raw && !chosen {
+        diag!("Feeding raw 0-255 pixels as the model metadata asks (pixel_range)");
+        config.normalization = Normalization::RAW;
+        detector.set_normalization(Normalization::RAW);
+    } else if raw != feeding_raw {
+        let (expected, given) = if raw { ("raw 0-255", "0-1") } else { ("0-1", "raw 0-255") };
+        diag!("Warning: the model metadata says it takes {expected} pixels, but they are fed as {given}");
+    }
 }
 
-fn is_image_file(path: &Path) -> bool {
-    path.extension().is_some_and(|ext| {
-        let ext = ext.to_string_lossy().to_lowercase();
-        matches!(
-            ext.as_str(),
-            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "tiff" | "tif"
-        )
-    })
+fn parse_input_size(s: &str) -> Result<u32, String> {
+    match s.trim().parse::<u32>() {
+        Ok(size) if size > 0 && size % 32 == 0 => Ok(size),
+        _ => Err(format!("'{s}' is not a positive multiple of 32")),
+    }
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit_ms) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 1.0)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1000.0)
+    } else {
+        (s, 1.0)
+    };
 
-    // Check if model file exists
-    if !args.model.exists() {
-        eprintln!("Error: Model file not found at {}", args.model.display());
-        eprintln!("Please run ./download_models.sh to download the YOLOv8 model.");
-        std::process::exit(1);
+    match number.trim().parse::<f64>() {
+        Ok(value) if value >= 0.0 && value.is_finite() => Ok(Duration::from_secs_f64(value * unit_ms / 1000.0)),
+        _ => Err(format!("'{s}' is not a duration such as 500ms or 2s")),
     }
+}
 
-    if args.verbose {
-        eprintln!("Loading YOLOv8 model from {}...", args.model.display());
+fn parse_distance(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(distance) if distance >= 0.0 && distance.is_finite() => Ok(distance),
+        _ => Err(format!("'{s}' is not a distance in pixels such as 40")),
     }
+}
 
-    // Initialize detector
-    let detector = YoloCatDetector::new(&args.model, args.confidence)?;
+fn parse_shard(s: &str) -> Result<Shard, String> {
+    let invalid = || format!("'{s}' is not a shard such as 0/4");
+    let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+    let index = index.trim().parse::<usize>().map_err(|_| invalid())?;
+    let count = count.trim().parse::<usize>().map_err(|_| invalid())?;
 
-    if args.verbose {
-        eprintln!("Model loaded successfully!");
-        eprintln!("Scanning directory: {}", args.path.display());
-        eprintln!("Confidence threshold: {}", args.confidence);
+    if count == 0 || index >= count {
+        return Err(format!("shard {index} does not exist in {count} shards (counted from 0)"));
     }
+    Ok(Shard { index, count })
+}
 
-    let mut found_count = 0;
-    let mut total_count = 0;
-    let mut error_count = 0;
+/// A confidence as a fraction (`0.25`) or a percentage (`25%`)
+fn parse_confidence(s: &str) -> Result<f32, String> {
+    let s = s.trim();
+    let (number, scale) = match s.strip_suffix('%') {
+        Some(percent) => (percent.trim(), 100.0),
+        None => (s, 1.0),
+    };
+    let confidence = number
+        .parse::<f32>()
+        .map_err(|_| format!("'{s}' is not a confidence such as 0.25 or 25%"))?
+        / scale;
 
-    for entry in WalkDir::new(&args.path)
-        .follow_links(true)
-        .into_iter()
-        .filter_map(|e| e.ok())
-    {
-        let path = entry.path();
+    if !(0.0..=1.0).contains(&confidence) {
+        return Err(if scale == 1.0 && (1.0..=100.0).contains(&confidence) {
+            format!("'{s}' is not between 0 and 1; for a percentage write {s}%")
+        } else {
+            format!("'{s}' is not between 0 and 1 (0% and 100%)")
+        });
+    }
+    Ok(confidence)
+}
 
-        if !path.is_file() || !is_image_file(path) {
-            continue;
+fn parse_confidence_range(s: &str) -> Result<(f32, f32), String> {
+    let invalid = || format!("'{s}' is not a range such as 0.15-0.25");
+    let (low, high) = s.split_once('-').ok_or_else(invalid)?;
+    let low = low.trim().parse::<f32>().map_err(|_| invalid())?;
+    let high = high.trim().parse::<f32>().map_err(|_| invalid())?;
+
+    if !(0.0 <= low && low < high && high <= 1.0) {
+        return Err(format!("'{s}' must satisfy 0 <= LOW < HIGH <= 1"));
+    }
+    Ok((low, high))
+}
+
+/// Threshold the detector runs at: --confidence, or the bottom of --maybe-range
+/// when lower so borderline detections reach the maybe check
+fn detection_threshold(args: &Args) -> f32 {
+    args.maybe_range.map_or(args.confidence, |(low, _)| low.min(args.confidence))
+}
+
+/// What a `--sqlite` result must have been scanned with for `--skip-known` to
+/// reuse it: the model (by SHA-256, or the inference server), the mask (by
+/// SHA-256), the detector's effective `config` and the scan `options` that
+/// change what is found
+fn scan_cache_settings(args: &Args, config: &DetectorConfig, options: &ScanOptions) -> Result<String> {
+    let model = match (&args.remote_url, &args.classifier) {
+        (Some(url), _) => format!("remote:{url}"),
+        (None, Some(classifier)) => calculate_sha256(classifier)?,
+        (None, None) => calculate_sha256(&args.model)?,
+    };
+    let mask = args.mask.as_deref().map(calculate_sha256).transpose()?;
+
+    // The debug dump never changes the detections
+    let mut config = DetectorConfig {
+        debug_top_k: 0,
+        debug_min_conf: 0.0,
+        ..config.clone()
+    };
+    config.classes.sort_unstable();
+    config.classes.dedup();
+
+    Ok(format!(
+        "model={model};mask={mask:?};config={config:?};rotations={};tta={};scales={:?};max_pixels={:?};\
+         jpeg_dct_scaling={};frame_agg={:?};thumbnail_prefilter={:?}",
+        options.try_rotations,
+        options.tta,
+        options.scales,
+        options.max_pixels,
+        options.jpeg_dct_scaling,
+        options.frame_agg,
+        options.thumbnail_prefilter,
+    ))
+}
+
+fn parse_class(s: &str) -> Result<usize, String> {
+    class_id(s).ok_or_else(|| format!("unknown class '{s}' (see --print-classes)"))
+}
+
+fn parse_alias(s: &str) -> Result<(usize, usize), String> {
+    let (from, to) = s.split_once('=').ok_or_else(|| format!("'{s}' is not FROM=TO, e.g. 77=cat"))?;
+    Ok((parse_class(from)?, parse_class(to)?))
+}
+
+fn parse_rgb_triplet(s: &str) -> Result<[f32; 3], String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f32>().map_err(|e| format!("invalid number '{}': {e}", v.trim())))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    <[f32; 3]>::try_from(values).map_err(|_| "expected three comma-separated values (r,g,b)".to_string())
+}
+
+/// Newline- (or NUL-) separated list of paths written alongside the scan
+struct PathList {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    terminator: u8,
+}
+
+impl PathList {
+    fn create(path: &Path, print0: bool) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create list file: {}", path.display()))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            terminator: if print0 { b'\0' } else { b'\n' },
+        })
+    }
+
+    fn add(&mut self, entry: &Path) -> Result<()> {
+        self.writer
+            .write_all(entry.as_os_str().as_encoded_bytes())
+            .and_then(|()| self.writer.write_all(&[self.terminator]))
+            .with_context(|| format!("Failed to write list file: {}", self.path.display()))
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .with_context(|| format!("Failed to write list file: {}", self.path.display()))
+    }
+}
+
+/// Most matched paths passed to one --exec-batch invocation, well below
+/// common argument length limits
+const EXEC_BATCH_SIZE: usize = 256;
+
+/// The --exec command, run on matched files one at a time or, with
+/// --exec-batch, on many at once.
+///
+/// The command is split into words like a shell would, but never run
+/// through one: paths replace `{}` as plain arguments, so spaces, quotes
+/// and `$` in file names need no escaping.
+struct ExecHook {
+    program: String,
+    args: Vec<String>,
+    // Paths waiting for the next --exec-batch invocation
+    queue: Option<Vec<PathBuf>>,
+}
+
+impl ExecHook {
+    fn new(command: &str, batch: bool) -> Result<Self> {
+        let words = shlex::split(command).context("--exec command has unbalanced quotes")?;
+        let Some((program, args)) = words.split_first() else {
+            anyhow::bail!("--exec command is empty");
+        };
+        if !args.iter().any(|arg| arg.contains("{}")) {
+            anyhow::bail!("--exec command needs a {{}} argument where the matched path goes");
+        }
+        if batch && args.iter().any(|arg| arg.contains("{}") && arg != "{}") {
+            anyhow::bail!("with --exec-batch, {{}} must be an argument of its own");
         }
 
-        total_count += 1;
+        Ok(Self {
+            program: program.clone(),
+            args: args.to_vec(),
+            queue: batch.then(Vec::new),
+        })
+    }
 
-        if args.verbose {
-            eprint!("Analyzing: {} ... ", path.display());
+    /// Run the command on `path`, or queue it for the next batch. Returns
+    /// the exit status of a command that failed.
+    fn add(&mut self, path: &Path) -> Result<Option<ExitStatus>> {
+        let Some(queue) = &mut self.queue else {
+            return self.run(&[path.to_path_buf()]);
+        };
+
+        queue.push(path.to_path_buf());
+        if queue.len() < EXEC_BATCH_SIZE {
+            return Ok(None);
         }
+        self.flush()
+    }
 
-        match detector.detect_cats(path) {
-            Ok(has_cats) => {
-                if args.verbose {
-                    eprintln!("{}", if has_cats { "CAT FOUND!" } else { "no cats" });
-                }
+    /// Run the command on the paths queued for a batch, if any
+    fn flush(&mut self) -> Result<Option<ExitStatus>> {
+        let paths = self.queue.as_mut().map(std::mem::take).unwrap_or_default();
+        if paths.is_empty() {
+            return Ok(None);
+        }
+        self.run(&paths)
+    }
 
-                if has_cats {
-                    found_count += 1;
-
-                    if args.timestamp {
-                        if let Some((timestamp, source)) = get_image_timestamp(path) {
-                            println!(
-                                "{} [{}:{}]",
-                                path.display(),
-                                source,
-                                timestamp.format("%Y-%m-%d %H:%M:%S")
-                            );
-                        } else {
-                            println!("{}", path.display());
-                        }
-                    } else {
-                        println!("{}", path.display());
-                    }
-                }
-            }
-            Err(e) => {
-                error_count += 1;
-                if args.verbose {
-                    eprintln!("ERROR: {e:?}");
-                }
+    fn run(&self, paths: &[PathBuf]) -> Result<Option<ExitStatus>> {
+        let mut command = Command::new(&self.program);
+        for arg in &self.args {
+            if arg == "{}" {
+                command.args(paths);
+            } else {
+                // Only a single path can sit inside a longer argument (checked in new)
+                command.arg(substitute_path(arg, &paths[0]));
             }
         }
+
+        // The scan may be reading paths from stdin
+        let status = command
+            .stdin(Stdio::null())
+            .status()
+            .with_context(|| format!("Failed to run --exec command '{}'", self.program))?;
+        Ok((!status.success()).then_some(status))
     }
+}
 
-    if args.verbose {
-        eprintln!();
-        eprintln!("Summary:");
-        eprintln!("  Total images scanned: {total_count}");
-        eprintln!("  Images with cats: {found_count}");
-        if error_count > 0 {
-            eprintln!("  Errors: {error_count}");
+/// `arg` with each `{}` replaced by `path`, keeping non-UTF-8 paths intact
+fn substitute_path(arg: &str, path: &Path) -> OsString {
+    let mut substituted = OsString::new();
+    for (index, part) in arg.split("{}").enumerate() {
+        if index > 0 {
+            substituted.push(path);
         }
+        substituted.push(part);
     }
+    substituted
+}
 
-    Ok(())
+/// `path,count,max_conf,width,height` rows for every analyzed image
+struct AnalysisCsv {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl AnalysisCsv {
+    fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create CSV file: {}", path.display()))?;
+        let mut csv = Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+        };
+        csv.write_line("path,count,max_conf,width,height")?;
+        Ok(csv)
+    }
+
+    fn add(&mut self, result: &ScanResult) -> Result<()> {
+        let (width, height) = result.dimensions.unwrap_or_default();
+        let line = format!(
+            "{},{},{:.4},{width},{height}",
+            csv_field(&result.path.display().to_string()),
+            result.detections.len(),
+            best_confidence(&result.detections)
+        );
+        self.write_line(&line)
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.writer, "{line}").with_context(|| format!("Failed to write CSV file: {}", self.path.display()))
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .with_context(|| format!("Failed to write CSV file: {}", self.path.display()))
+    }
+}
+
+/// Read a path list as written by [`PathList`], NUL- or newline-separated
+fn read_path_list(path: &Path) -> Result<Vec<PathBuf>> {
+    let contents = fs::read(path).with_context(|| format!("Failed to read path list: {}", path.display()))?;
+    let separator = if contents.contains(&b'\0') { b'\0' } else { b'\n' };
+
+    Ok(contents
+        .split(|&byte| byte == separator)
+        .map(|entry| String::from_utf8_lossy(entry).trim_end_matches('\r').to_string())
+        .filter(|entry| !entry.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Progress snapshot written by --heartbeat
+#[derive(Serialize)]
+struct Progress {
+    processed: usize,
+    total: usize,
+    found: usize,
+    errors: usize,
+    /// Extrapolated from the average time per image so far
+    eta_seconds: Option<u64>,
+}
+
+/// Canonical paths of the results printed so far, for --dedupe-output
+#[derive(Default)]
+struct PrintedPaths {
+    paths: HashSet<PathBuf>,
+    suppressed: usize,
+}
+
+impl PrintedPaths {
+    /// Whether `path` leads to a file not printed yet; repeats are counted
+    fn first(&mut self, path: &Path) -> bool {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let first = self.paths.insert(canonical);
+        if !first {
+            self.suppressed += 1;
+        }
+        first
+    }
+}
+
+/// A match ranked by --report-largest; ordered by `size` first
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct SizedMatch {
+    size: u64,
+    path: PathBuf,
+    dimensions: Option<(u32, u32)>,
+}
+
+/// The largest matches seen so far, for --report-largest
+struct Largest {
+    count: usize,
+    by_pixels: bool,
+    // Smallest on top, so it is the one dropped when a larger match arrives
+    kept: BinaryHeap<Reverse<SizedMatch>>,
+}
+
+impl Largest {
+    fn new(count: u32, by_pixels: bool) -> Self {
+        Self {
+            count: count as usize,
+            by_pixels,
+            kept: BinaryHeap::new(),
+        }
+    }
+
+    /// Consider a matched image; one whose size is unknown is left out
+    fn add(&mut self, result: &ScanResult) {
+        let size = if self.by_pixels {
+            result.dimensions.map(|(width, height)| u64::from(width) * u64::from(height))
+        } else {
+            fs::metadata(&result.path).ok().map(|metadata| metadata.len())
+        };
+        let Some(size) = size else {
+            return;
+        };
+
+        self.kept.push(Reverse(SizedMatch {
+            size,
+            path: result.path.clone(),
+            dimensions: result.dimensions,
+        }));
+        if self.kept.len() > self.count {
+            self.kept.pop();
+        }
+    }
+
+    /// Print the kept matches on stdout, largest first: a JSON record each
+    /// with --format json, bare paths with --print0, else size and path.
+    /// A COCO document leaves no room for them, so they go to stderr then.
+    fn report(self, args: &Args) {
+        let by_pixels = self.by_pixels;
+        diag!("Largest matches by {}: {}", if by_pixels { "pixel count" } else { "file size" }, self.kept.len());
+        // Ascending order of the reversed entries is descending size
+        for Reverse(SizedMatch { size, path, dimensions }) in self.kept.into_sorted_vec() {
+            if args.format == OutputFormat::Json {
+                let record = LargestRecord {
+                    path: path.display().to_string(),
+                    bytes: (!by_pixels).then_some(size),
+                    pixels: by_pixels.then_some(size),
+                    width: dimensions.map(|(width, _)| width),
+                    height: dimensions.map(|(_, height)| height),
+                };
+                emit(&record_json(&record, args), false);
+                continue;
+            }
+            if args.print0 {
+                emit(&path.display().to_string(), true);
+                continue;
+            }
+
+            let size = match dimensions {
+                Some((width, height)) if by_pixels => format!("{width}x{height}"),
+                _ if size >= 1 << 20 => format!("{:.1} MiB", size as f64 / f64::from(1 << 20)),
+                _ => format!("{} KiB", size.div_ceil(1024)),
+            };
+            let line = format!("{size:>11}  {}", path.display());
+            if args.format == OutputFormat::Coco {
+                diag!("  {line}");
+            } else {
+                emit(&line, false);
+            }
+        }
+    }
+}
+
+/// One --report-largest entry under --format json
+#[derive(Serialize)]
+struct LargestRecord {
+    path: String,
+    /// File size, when ranked by it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<u64>,
+    /// Pixel count, with --by-pixels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pixels: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+}
+
+/// Counts of a whole scan, written by --stats
+#[derive(Serialize)]
+struct ScanStats {
+    /// Entries the walk (or --rescan) listed, skipped or not
+    encountered: usize,
+    /// Entries passed over before analysis, per reason (e.g. `not-an-image`)
+    skipped: BTreeMap<String, usize>,
+    /// Images analyzed, including the ones that failed
+    processed: usize,
+    found: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maybe: Option<usize>,
+    errors: usize,
+    oversized: usize,
+    timed_out: usize,
+    duplicates: usize,
+    /// Repeated results left unprinted by --dedupe-output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duplicate_outputs: Option<usize>,
+    prefiltered: usize,
+    inferences: usize,
+    /// Images processed and found per lowercase file extension
+    extensions: BTreeMap<String, ExtensionCounts>,
+    /// False when the scan stopped early (--max-errors, --max-inferences, closed output)
+    complete: bool,
+}
+
+/// Images of one file extension in the summary and --stats
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+struct ExtensionCounts {
+    processed: usize,
+    found: usize,
+}
+
+/// Lowercase extension of an image path, the key of [`ExtensionCounts`]
+fn extension_key(path: &Path) -> String {
+    path.extension().map_or_else(String::new, |ext| ext.to_string_lossy().to_lowercase())
+}
+
+impl ScanStats {
+    fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).expect("stats serialize to JSON");
+        fs::write(path, json + "\n").with_context(|| format!("Failed to write stats to {}", path.display()))
+    }
+}
+
+/// Overwrites a JSON progress file at most once per interval
+struct Heartbeat {
+    path: PathBuf,
+    interval: Duration,
+    total: usize,
+    started: Instant,
+    last_write: Option<Instant>,
+}
+
+impl Heartbeat {
+    fn new(path: &Path, interval: Duration, total: usize) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            interval,
+            total,
+            started: Instant::now(),
+            last_write: None,
+        }
+    }
+
+    /// Write a snapshot if the interval has passed since the last one
+    fn tick(&mut self, processed: usize, found: usize, errors: usize) -> Result<()> {
+        if self.last_write.is_some_and(|last| last.elapsed() < self.interval) {
+            return Ok(());
+        }
+        self.write(processed, found, errors)
+    }
+
+    fn write(&mut self, processed: usize, found: usize, errors: usize) -> Result<()> {
+        let remaining = self.total.saturating_sub(processed);
+        let eta_seconds = (processed > 0).then(|| {
+            (self.started.elapsed().as_secs_f64() / processed as f64 * remaining as f64).round() as u64
+        });
+        let progress = Progress {
+            processed,
+            total: self.total.max(processed),
+            found,
+            errors,
+            eta_seconds,
+        };
+
+        // Replace the file atomically so a poller never reads half a snapshot
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        let json = serde_json::to_string(&progress).expect("progress serializes");
+        fs::write(&temp, json + "\n")
+            .and_then(|()| fs::rename(&temp, &self.path))
+            .with_context(|| format!("Failed to write heartbeat: {}", self.path.display()))?;
+
+        self.last_write = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// Hash the file of `result` for --with-hash unless the scan already did
+fn hash_for_output(result: &mut ScanResult, args: &Args) -> Result<(), DetectError> {
+    if args.with_hash && result.sha256.is_none() {
+        result.sha256 = Some(calculate_sha256_with_buffer(&result.path, args.hash_buffer_kb as usize * 1024)?);
+    }
+
+    Ok(())
+}
+
+fn image_record(result: &ScanResult, args: &Args) -> ImageRecord {
+    ImageRecord {
+        path: result.path.display().to_string(),
+        timestamp: result
+            .timestamp
+            .filter(|_| args.timestamp)
+            .map(|(timestamp, _)| args.timestamp_format.format(&timestamp)),
+        sha256: result.sha256.clone().filter(|_| args.with_hash),
+        exif: args
+            .with_exif
+            .then(|| PhotoMetadata::read(&result.path))
+            .filter(|metadata| !metadata.is_empty()),
+        rotation: result.rotation,
+        detections: result.detections.iter().map(Into::into).collect(),
+        scales: result.scales.clone(),
+        maybe: false,
+    }
+}
+
+/// Best confidence per input size, e.g. `640=0.81 960=- 1280=0.90`
+fn format_scales(result: &ScanResult) -> String {
+    result
+        .scales
+        .iter()
+        .map(|scale| match scale.best_confidence {
+            Some(confidence) => format!("{}={confidence:.2}", scale.input_size),
+            None => format!("{}=-", scale.input_size),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether a result counts as a match for --min-cats
+fn is_match(result: &ScanResult, min_cats: usize) -> bool {
+    result.has_cats() && result.detections.len() >= min_cats
+}
+
+/// Per-class counts, e.g. `cat=2 dog=1`
+fn format_class_counts(result: &ScanResult) -> String {
+    result
+        .class_counts()
+        .iter()
+        .map(|(name, count)| format!("{name}={count}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Set once the reader of stdout is gone, e.g. `| head` has all its lines
+static OUTPUT_CLOSED: AtomicBool = AtomicBool::new(false);
+
+/// Print one result and flush it at once, so consumers such as `| grep` see
+/// each result as soon as it is found rather than when a buffer fills. `nul`
+/// ends it with NUL (--print0) instead of a newline. After the reader closes
+/// the pipe, output is dropped and [`output_closed`] tells scans to stop.
+fn emit(line: &str, nul: bool) {
+    if output_closed() {
+        return;
+    }
+
+    let mut stdout = io::stdout().lock();
+    let terminator: &[u8] = if nul { b"\0" } else { b"\n" };
+    let written = stdout
+        .write_all(line.as_bytes())
+        .and_then(|()| stdout.write_all(terminator))
+        .and_then(|()| stdout.flush());
+
+    match written {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => OUTPUT_CLOSED.store(true, Ordering::Relaxed),
+        Err(e) => panic!("failed printing to stdout: {e}"),
+    }
+}
+
+/// Whether stdout's reader has gone away, so printing more is pointless
+fn output_closed() -> bool {
+    OUTPUT_CLOSED.load(Ordering::Relaxed)
+}
+
+fn print_match(result: &ScanResult, args: &Args) {
+    // COCO output is one document, collected during the scan and printed at the end
+    if args.format == OutputFormat::Coco {
+        return;
+    }
+
+    if args.format == OutputFormat::Json {
+        emit(&record_json(&image_record(result, args), args), false);
+        return;
+    }
+
+    let mut line = result.path.display().to_string();
+
+    if args.class.len() > 1 {
+        line.push_str(&format!(": {}", format_class_counts(result)));
+    }
+
+    if args.timestamp && let Some((timestamp, source)) = result.timestamp {
+        line.push_str(&format!(" [{}:{}]", source, args.timestamp_format.format(&timestamp)));
+    }
+
+    if result.rotation != 0 {
+        line.push_str(&format!(" [rot:{}]", result.rotation));
+    }
+
+    if !result.scales.is_empty() {
+        line.push_str(&format!(" [scales: {}]", format_scales(result)));
+    }
+
+    if args.with_hash && let Some(sha256) = &result.sha256 {
+        line.push_str(&format!(" [SHA-256: {sha256}]"));
+    }
+
+    emit(&line, args.print0);
+}
+
+/// A record as printed: one line, or indented over several with --json-pretty
+fn record_json(record: &impl Serialize, args: &Args) -> String {
+    let json = if args.json_pretty {
+        serde_json::to_string_pretty(record)
+    } else {
+        serde_json::to_string(record)
+    };
+    json.expect("records serialize")
+}
+
+/// Highest confidence among `detections`, 0 when there are none
+fn best_confidence(detections: &[Detection]) -> f32 {
+    detections.iter().map(|d| d.confidence).fold(0.0, f32::max)
+}
+
+/// One --maybe-range image with its borderline detections: `? path [maybe: 0.19]`,
+/// or a JSON record with `"maybe": true`
+fn print_maybe(result: &ScanResult, args: &Args) {
+    // COCO datasets only hold matches
+    if args.format == OutputFormat::Coco {
+        return;
+    }
+
+    if args.format == OutputFormat::Json {
+        let record = ImageRecord {
+            maybe: true,
+            ..image_record(result, args)
+        };
+        emit(&record_json(&record, args), false);
+        return;
+    }
+
+    let line = format!("? {} [maybe: {:.2}]", result.path.display(), best_confidence(&result.detections));
+    emit(&line, args.print0);
+}
+
+/// Verbose status for one analyzed image, scanned for `classes`
+fn describe_result(result: &ScanResult, is_match: bool, classes: &[usize]) -> String {
+    let count = result.detections.len();
+    let multi_class = classes.len() > 1;
+    let name = classes.first().and_then(|&class_id| YOLO_CLASSES.get(class_id)).copied().unwrap_or("cat");
+    let mut status = if multi_class {
+        match (is_match, count) {
+            (true, _) => format!("FOUND! {}", format_class_counts(result)),
+            (false, 0) => "nothing found".to_string(),
+            (false, _) => format!("{}, below --min-cats", format_class_counts(result)),
+        }
+    } else if is_match {
+        format!("{} FOUND!", name.to_uppercase())
+    } else if count > 0 {
+        format!("{count} {name}(s), below --min-cats")
+    } else {
+        format!("no {name}s")
+    };
+
+    if is_match && count > 1 && !multi_class {
+        status.push_str(&format!(" ({count} {name}s)"));
+    }
+    if result.rotation != 0 {
+        status.push_str(&format!(" (rotated {}°)", result.rotation));
+    }
+    if !result.scales.is_empty() {
+        status.push_str(&format!(" (scales: {})", format_scales(result)));
+    }
+    if let Some(first) = &result.duplicate_of {
+        status.push_str(&format!(" (duplicate of {})", first.display()));
+    }
+
+    status
+}
+
+/// An error and its causes on one line, outermost first
+fn error_chain(error: &dyn std::error::Error) -> String {
+    std::iter::successors(Some(error), |e| e.source())
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// Print the details needed to reproduce a run: versions, execution providers and model hash
+fn print_diagnostics(args: &Args) {
+    println!("cat-finder {}", env!("CARGO_PKG_VERSION"));
+    println!("ONNX Runtime API version: {}", ort::sys::ORT_API_VERSION);
+    println!("Execution providers: {}", available_execution_providers().join(", "));
+    println!("Model: {}", args.model.display());
+
+    match calculate_sha256(&args.model) {
+        Ok(hash) => println!("Model SHA-256: {hash}"),
+        Err(e) => println!("Model SHA-256: unavailable ({})", error_chain(&e)),
+    }
+}
+
+/// Images timed by --estimate, after one untimed warm-up image
+const ESTIMATE_SAMPLES: usize = 5;
+
+/// Time the first few images of `scan` and project the time for all `total`
+fn run_estimate(mut scan: Scan, total: usize) {
+    let mut timings = Vec::new();
+    while timings.len() <= ESTIMATE_SAMPLES {
+        let started = Instant::now();
+        if scan.next().is_none() {
+            break;
+        }
+        timings.push(started.elapsed());
+    }
+
+    // The first image also pays for ONNX Runtime's warm-up
+    if timings.len() > 1 {
+        timings.remove(0);
+    }
+
+    println!("Images: {total}");
+    if timings.is_empty() {
+        println!("Estimated scan time: 0s");
+        return;
+    }
+
+    let per_image = timings.iter().sum::<Duration>() / timings.len() as u32;
+    println!("Sampled: {} images, {:.3}s per image", timings.len(), per_image.as_secs_f64());
+    // Duration arithmetic takes a u32; a larger count saturates the same way
+    let estimate = per_image.saturating_mul(u32::try_from(total).unwrap_or(u32::MAX));
+    println!("Estimated scan time: {}", format_duration(estimate));
+}
+
+/// `1h 02m 03s`, `2m 03s` or `3s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m {s:02}s"),
+        (h, m, s) => format!("{h}h {m:02}m {s:02}s"),
+    }
+}
+
+/// Best cat confidence in a result, if it counts as a match
+fn match_confidence(result: &ScanResult, min_cats: usize) -> Option<f32> {
+    is_match(result, min_cats)
+        .then(|| best_confidence(&result.detections))
+}
+
+/// Write the model input for `path` as an image, de-normalized
+fn dump_preprocessed(path: &Path, out: &Path, normalization: Normalization, args: &Args) -> Result<()> {
+    let config = DetectorConfig {
+        normalization,
+        channel_order: args.channel_order.into(),
+        resize_filter: args.resize_filter.into(),
+        ..DetectorConfig::default()
+    };
+    let input_size = args.multi_scale.first().copied().unwrap_or(INPUT_SIZE);
+    // Decoded as the scan would, so --jpeg-dct-scaling shows up in the dump
+    let decode = ScanOptions {
+        max_pixels: args.max_pixels,
+        jpeg_dct_scaling: args.jpeg_dct_scaling,
+        scales: args.multi_scale.clone(),
+        ..ScanOptions::default()
+    };
+
+    let (img, (width, height)) = decode_for_scan(path, &decode)?;
+    let input = ModelInput::new(&img, input_size, &config);
+    input
+        .to_rgb_image(&normalization)
+        .save(out)
+        .with_context(|| format!("Failed to write {}", out.display()))?;
+
+    diag!(
+        "Wrote the {input_size}x{input_size} model input for {} ({}x{}) to {}",
+        path.display(),
+        width,
+        height,
+        out.display()
+    );
+    Ok(())
+}
+
+/// The crop encoding chosen by --output-format and --output-quality
+fn crop_format(args: &Args) -> CropFormat {
+    match args.output_format {
+        CropFormatArg::Jpg => CropFormat::Jpeg {
+            quality: args.output_quality.unwrap_or(DEFAULT_CROP_QUALITY),
+        },
+        CropFormatArg::Png => CropFormat::Png,
+        CropFormatArg::Webp => CropFormat::WebP,
+    }
+}
+
+/// Write the raw model output for the image at `path` as a NumPy array
+fn dump_output(detector: &YoloCatDetector, path: &Path, out: &Path, args: &Args) -> Result<()> {
+    let input_size = args.multi_scale.first().copied().unwrap_or(INPUT_SIZE);
+
+    let img = open_image(path, args.max_pixels)?;
+    let output = detector.raw_output(&ModelInput::new(&img, input_size, detector.config()))?;
+    write_npy(out, output.shape(), output.iter().copied())
+        .with_context(|| format!("Failed to write {}", out.display()))?;
+
+    diag!("Wrote the {:?} model output for {} to {}", output.shape(), path.display(), out.display());
+    Ok(())
+}
+
+/// Write `values` (in row-major order) as a little-endian float32 array of
+/// `shape` in NumPy's `.npy` format, version 1.0
+fn write_npy(path: &Path, shape: &[usize], values: impl Iterator<Item = f32>) -> io::Result<()> {
+    let dims: Vec<String> = shape.iter().map(ToString::to_string).collect();
+    // A one-element tuple needs its trailing comma
+    let shape = match dims.as_slice() {
+        [dim] => format!("({dim},)"),
+        dims => format!("({})", dims.join(", ")),
+    };
+
+    // Magic, version and header length take 10 bytes; the header is padded
+    // with spaces and ends in a newline, so the data starts 64-byte aligned
+    let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {shape}, }}");
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x93NUMPY\x01\x00")?;
+    file.write_all(&(header.len() as u16).to_le_bytes())?;
+    file.write_all(header.as_bytes())?;
+    for value in values {
+        file.write_all(&value.to_le_bytes())?;
+    }
+    file.flush()
+}
+
+/// Run two models over the same images and print the paths where only one finds a cat
+fn run_compare(
+    detector: &dyn CatDetector,
+    second: &dyn CatDetector,
+    second_model: &Path,
+    options: &ScanOptions,
+    args: &Args,
+) -> Result<()> {
+    let format_confidence =
+        |confidence: Option<f32>| confidence.map_or("-".to_string(), |c| format!("{c:.2}"));
+
+    let mut compared = 0;
+    let mut both = 0;
+    let mut only_first = 0;
+    let mut only_second = 0;
+    let mut error_count = 0;
+
+    // B rescans each file A's walk found as a root of its own. A already
+    // applied --min-depth and --shard, relative to PATH; B's shard would be
+    // taken from the bare file name and could disagree
+    let file_options = ScanOptions {
+        min_depth: 0,
+        shard: None,
+        ..options.clone()
+    };
+    for result in detector.scan(&args.path, options.clone()) {
+        let other = second.scan(&result.path, file_options.clone()).next();
+        let errors = [result.error.as_ref(), other.as_ref().and_then(|r| r.error.as_ref())];
+
+        if let Some(e) = errors.into_iter().flatten().next() {
+            error_count += 1;
+            if args.verbose {
+                diag!("Comparing: {} ... ERROR: {}", result.path.display(), error_chain(e));
+            }
+            continue;
+        }
+        let Some(other) = other else {
+            error_count += 1;
+            if args.verbose {
+                diag!("Comparing: {} ... ERROR: not analyzed with B", result.path.display());
+            }
+            continue;
+        };
+
+        compared += 1;
+        let first_hit = match_confidence(&result, args.min_cats);
+        let second_hit = match_confidence(&other, args.min_cats);
+
+        match (first_hit, second_hit) {
+            (Some(_), Some(_)) => both += 1,
+            (Some(_), None) => only_first += 1,
+            (None, Some(_)) => only_second += 1,
+            (None, None) => {}
+        }
+
+        if first_hit.is_some() != second_hit.is_some() {
+            let line = format!(
+                "{}\tA: {}\tB: {}",
+                result.path.display(),
+                format_confidence(first_hit),
+                format_confidence(second_hit)
+            );
+            emit(&line, false);
+            if output_closed() {
+                break;
+            }
+        }
+    }
+
+    if args.verbose {
+        diag!();
+        diag!("Comparison (A = {}, B = {}):", args.model.display(), second_model.display());
+        diag!("  Images compared: {compared}");
+        diag!("  Both found cats: {both}");
+        diag!("  Only A: {only_first}");
+        diag!("  Only B: {only_second}");
+        if error_count > 0 {
+            diag!("  Errors: {error_count}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Analyze images as they land below PATH, until interrupted
+fn run_watch(detector: &dyn CatDetector, options: &ScanOptions, args: &Args) -> Result<()> {
+    let mut watcher = FileWatcher::new(&args.path, args.watch_settle)
+        .with_context(|| format!("Failed to watch {}", args.path.display()))?;
+    let color = use_color(args);
+    let mut exec_hook = args.exec.as_deref().map(|command| ExecHook::new(command, args.exec_batch)).transpose()?;
+
+    if args.verbose {
+        diag!("Watching {} for new images (Ctrl-C to stop)", args.path.display());
+    }
+
+    loop {
+        let mut paths = watcher.wait()?;
+        if paths.is_empty() {
+            return Ok(());
+        }
+        // Each file is scanned as a root of its own, so the walk's filters
+        // are applied here, relative to the watched directory
+        paths.retain(|path| {
+            let relative = path.strip_prefix(&args.path).unwrap_or(path);
+            !(options.skip_hidden && is_hidden(relative))
+                && relative.components().count() >= options.min_depth
+                && options.shard.is_none_or(|shard| shard.contains(relative))
+        });
+        if paths.is_empty() {
+            continue;
+        }
+
+        let file_options = ScanOptions {
+            min_depth: 0,
+            shard: None,
+            ..options.clone()
+        };
+        for mut result in detector.scan_paths(paths, file_options) {
+            // Without a summary at the end, failures are reported as they happen
+            if let Some(e) = &result.error {
+                diag!("Analyzing: {} ... {}: {}", result.path.display(), paint("ERROR", RED, color), error_chain(e));
+                continue;
+            }
+
+            let is_match = is_match(&result, args.min_cats);
+            if args.verbose {
+                let status = describe_result(&result, is_match, &args.class);
+                let status = if is_match { paint(&status, GREEN, color) } else { status };
+                diag!("Analyzing: {} ... {}", result.path.display(), status);
+            }
+            if !is_match {
+                continue;
+            }
+
+            if args.with_hash && result.sha256.is_none() {
+                result.sha256 = calculate_sha256_with_buffer(&result.path, args.hash_buffer_kb as usize * 1024).ok();
+            }
+            print_match(&result, args);
+            if output_closed() {
+                return Ok(());
+            }
+
+            if let Some(hook) = &mut exec_hook
+                && let Some(status) = hook.add(&result.path)?
+            {
+                diag!("ERROR running --exec on {}: {status}", result.path.display());
+            }
+        }
+
+        // Each batch of new files is one --exec-batch invocation
+        if let Some(hook) = &mut exec_hook
+            && let Some(status) = hook.flush()?
+        {
+            diag!("ERROR running --exec on the new matches: {status}");
+        }
+    }
+}
+
+/// Print the records of several --format json outputs, skipping any whose
+/// content (by SHA-256) an earlier record already covers
+fn run_merge(files: &[PathBuf], args: &Args) -> Result<()> {
+    // SHA-256 -> path of the record kept for it
+    let mut kept: HashMap<String, String> = HashMap::new();
+    let mut merged = Vec::new();
+    let mut total = 0;
+
+    // Everything is read before printing, so a bad file leaves no partial output
+
+    for file in files {
+        let records = read_records(file).with_context(|| format!("Failed to merge {}", file.display()))?;
+
+        for record in records {
+            total += 1;
+            let Some(sha256) = &record.sha256 else {
+                anyhow::bail!(
+                    "{}: {} has no sha256; merge only outputs of runs with --format json --with-hash",
+                    file.display(),
+                    record.path
+                );
+            };
+
+            if let Some(first) = kept.get(sha256) {
+                if args.verbose {
+                    diag!("Duplicate: {} (same content as {first})", record.path);
+                }
+                continue;
+            }
+
+            kept.insert(sha256.clone(), record.path.clone());
+            merged.push(record);
+        }
+    }
+
+    for record in &merged {
+        emit(&record_json(record, args), false);
+    }
+
+    if args.verbose {
+        diag!();
+        diag!("Merged {} files:", files.len());
+        diag!("  Records read: {total}");
+        diag!("  Unique images: {}", merged.len());
+        diag!("  Duplicates dropped: {}", total - merged.len());
+    }
+
+    Ok(())
+}
+
+/// Run detection over a labeled manifest and report precision/recall/F1
+fn run_eval(
+    detector: &dyn CatDetector,
+    manifest: &Path,
+    options: &ScanOptions,
+    args: &Args,
+) -> Result<()> {
+    let entries = read_manifest(manifest)?;
+    let color = use_color(args);
+    let mut matrix = ConfusionMatrix::default();
+    let mut error_count = 0;
+
+    // Each entry is scanned as a root of its own, which --min-depth or
+    // --shard would otherwise drop
+    let file_options = ScanOptions {
+        min_depth: 0,
+        shard: None,
+        ..options.clone()
+    };
+    for entry in &entries {
+        let Some(result) = detector.scan(&entry.path, file_options.clone()).next() else {
+            error_count += 1;
+            if args.verbose {
+                diag!("Evaluating: {} ... ERROR: not an image file", entry.path.display());
+            }
+            continue;
+        };
+
+        if let Some(e) = &result.error {
+            error_count += 1;
+            if args.verbose {
+                diag!("Evaluating: {} ... ERROR: {}", entry.path.display(), error_chain(e));
+            }
+            continue;
+        }
+
+        let predicted = is_match(&result, args.min_cats);
+        matrix.record(predicted, entry.has_cat);
+
+        if args.verbose {
+            let verdict = match (predicted, entry.has_cat) {
+                (true, true) => paint("true positive", GREEN, color),
+                (true, false) => paint("FALSE POSITIVE", RED, color),
+                (false, false) => paint("true negative", GREEN, color),
+                (false, true) => paint("FALSE NEGATIVE", RED, color),
+            };
+            diag!("Evaluating: {} ... {}", entry.path.display(), verdict);
+        }
+    }
+
+    println!("Evaluated: {} images ({} errors)", matrix.total(), error_count);
+    println!("Confidence threshold: {}", args.confidence);
+    println!();
+    println!("Confusion matrix:");
+    println!("                 predicted cat  predicted no cat");
+    println!("  actual cat     {:>13}  {:>16}", matrix.true_positives, matrix.false_negatives);
+    println!("  actual no cat  {:>13}  {:>16}", matrix.false_positives, matrix.true_negatives);
+    println!();
+    println!("Precision: {:.3}", matrix.precision());
+    println!("Recall:    {:.3}", matrix.recall());
+    println!("F1:        {:.3}", matrix.f1());
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // Through the diagnostic sink, so --log-file records why the run ended
+            diag!("Error: {e:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<()> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let is_default = |id| matches.value_source(id) == Some(ValueSource::DefaultValue);
+
+    if let Some(path) = &args.log_file {
+        start_log(path, args.log_mode, args.log_quiet)?;
+    }
+
+    if args.trace.is_some() {
+        enable_tracing();
+    }
+
+    if args.diagnostics {
+        print_diagnostics(&args);
+        return Ok(());
+    }
+
+    if args.list_providers {
+        if !args.model.exists() {
+            anyhow::bail!(
+                "--list-providers starts a session on each provider and needs --model; not found at {}",
+                args.model.display()
+            );
+        }
+        for (name, available) in execution_providers() {
+            if !available {
+                println!("{name:<26} not available");
+                continue;
+            }
+            match probe_execution_provider(name, &args.model) {
+                Ok(()) => println!("{name:<26} ok"),
+                Err(e) => println!("{name:<26} failed: {}", error_chain(&e)),
+            }
+        }
+        return Ok(());
+    }
+
+    if args.print_classes {
+        for (id, name) in YOLO_CLASSES.iter().enumerate() {
+            println!("{id:>2}  {name}");
+        }
+        return Ok(());
+    }
+
+    // --merge always prints JSON
+    if args.json_pretty && args.format == OutputFormat::Text && args.merge.is_empty() {
+        anyhow::bail!("--json-pretty requires --format json or --format coco");
+    }
+
+    // The COCO document is only complete once the scan ends
+    if args.format == OutputFormat::Coco && (args.watch || args.match_dirs) {
+        anyhow::bail!("--format coco cannot be combined with --watch or --match-dirs");
+    }
+
+    if !args.merge.is_empty() {
+        return run_merge(&args.merge, &args);
+    }
+
+    if let Some(stop_at) = args.stop_at
+        && (stop_at as usize) < args.min_cats
+    {
+        anyhow::bail!("--stop-at {stop_at} stops before --min-cats {} can be reached", args.min_cats);
+    }
+
+    // PNG and WebP crops are lossless
+    if args.output_quality.is_some() && args.output_format != CropFormatArg::Jpg {
+        anyhow::bail!("--output-quality only applies to --output-format jpg");
+    }
+
+    if args.any_animal {
+        // The animals replace the default cat class but add to an explicit --class
+        if is_default("class") {
+            args.class.clear();
+        }
+        for id in ANIMAL_CLASS_IDS {
+            if !args.class.contains(&id) {
+                args.class.push(id);
+            }
+        }
+    }
+
+    let normalization = normalization_from_args(&args)?;
+
+    if let Some(out) = &args.dump_preprocessed {
+        return dump_preprocessed(&args.path, out, normalization, &args);
+    }
+
+    // Check if model file exists
+    if args.remote_url.is_none() && args.classifier.is_none() && !args.model.exists() {
+        anyhow::bail!(
+            "Model file not found at {}; run ./scripts/download_models.sh to download the YOLOv8 model, \
+             or pass its path with --model",
+            args.model.display()
+        );
+    }
+
+    if args.verbose {
+        match (&args.remote_url, &args.classifier) {
+            (Some(url), _) => diag!("Using inference server at {url}"),
+            (None, Some(classifier)) => diag!("Loading classifier from {}...", classifier.display()),
+            (None, None) => diag!("Loading YOLOv8 model from {}...", args.model.display()),
+        }
+    }
+
+    let mut config = DetectorConfig {
+        confidence: args.confidence,
+        iou_threshold: args.iou,
+        classes: args.class.clone(),
+        class_aliases: args.alias.clone(),
+        suppressed_classes: args.suppress.clone(),
+        calibration: args.calibration.as_deref().map(Calibration::load).transpose()?,
+        normalization,
+        channel_order: args.channel_order.into(),
+        layout: args.layout.map(TensorLayout::from),
+        resize_filter: args.resize_filter.into(),
+        debug_top_k: args.debug_top_k,
+        debug_min_conf: args.debug_min_conf,
+        adaptive_confidence: args.adaptive_confidence,
+        input_name: args.input_name.clone(),
+        output_name: args.output_name.clone(),
+        stop_at: args.stop_at.map(|stop_at| stop_at as usize),
+        zero_fill_channels: args.zero_fill_channels,
+        merge_distance: args.merge_distance,
+    };
+
+    // --normalize none is the default, so only its explicit use counts as a choice
+    let normalization_chosen = args.no_normalize || !is_default("normalize");
+
+    if let Some(out) = &args.dump_output {
+        let mut detector = YoloCatDetector::new(&args.model, config.clone())?;
+        apply_model_pixel_range(&mut detector, &mut config, normalization_chosen);
+        return dump_output(&detector, &args.path, out, &args);
+    }
+
+    // Read before --error-list can truncate the same file
+    let rescan_paths = args.rescan.as_deref().map(read_path_list).transpose()?;
+
+    // Initialize detector
+    let detector: Box<dyn CatDetector> = match (&args.remote_url, &args.classifier) {
+        (Some(url), _) => {
+            let config = DetectorConfig {
+                confidence: detection_threshold(&args),
+                ..config.clone()
+            };
+            Box::new(RemoteDetector::new(url, config))
+        }
+        (None, Some(classifier)) => {
+            let config = DetectorConfig {
+                confidence: detection_threshold(&args),
+                ..config.clone()
+            };
+            Box::new(CatClassifier::new(classifier, config)?)
+        }
+        (None, None) => {
+            let mut detector = YoloCatDetector::new(&args.model, config.clone())?;
+            apply_model_pixel_range(&mut detector, &mut config, normalization_chosen);
+
+            // Recommended thresholds apply unless given on the command line
+            let recommended = detector.model_thresholds();
+            if let Some(confidence) = recommended.confidence
+                && is_default("confidence")
+            {
+                diag!("Using confidence threshold {confidence} from model metadata");
+                args.confidence = confidence;
+            }
+            if let Some(iou) = recommended.iou_threshold
+                && is_default("iou")
+            {
+                diag!("Using IoU threshold {iou} from model metadata");
+                args.iou = iou;
+            }
+            detector.set_thresholds(detection_threshold(&args), args.iou);
+            (config.confidence, config.iou_threshold) = (args.confidence, args.iou);
+
+            Box::new(detector)
+        }
+    };
+
+    if args.verbose {
+        if args.remote_url.is_none() {
+            diag!("Model loaded successfully!");
+        }
+        match (&rescan_paths, &args.rescan) {
+            (Some(paths), Some(list)) => {
+                diag!("Rescanning {} paths from {}", paths.len(), list.display());
+            }
+            _ => diag!("Scanning directory: {}", args.path.display()),
+        }
+        diag!("Confidence threshold: {}", args.confidence);
+        if args.adaptive_confidence {
+            diag!("Adaptive confidence: scaled by image resolution");
+        }
+        if let Some((low, high)) = args.maybe_range {
+            diag!("Maybe range: {low}-{high}");
+        }
+        if args.min_cats > 1 {
+            diag!("Minimum cats per image: {}", args.min_cats);
+        }
+    }
+
+    let mut options = ScanOptions {
+        dedupe: args.dedupe,
+        try_rotations: args.try_rotations,
+        tta: args.tta,
+        min_depth: args.min_depth,
+        skip_hidden: !args.include_hidden,
+        shard: args.shard,
+        sort: args.sort.map(Into::into),
+        max_pixels: args.max_pixels,
+        jpeg_dct_scaling: args.jpeg_dct_scaling,
+        hash_buffer_size: args.hash_buffer_kb as usize * 1024,
+        explain_skips: args.explain_skips,
+        frame_agg: (!args.first_frame_only).then(|| args.frame_agg.into()),
+        scales: args.multi_scale.clone(),
+        mask: args.mask.as_deref().map(IgnoreMask::load).transpose()?.map(Arc::new),
+        preprocess_pool: args
+            .preprocess_threads
+            .map(|threads| {
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads.into())
+                    .thread_name(|index| format!("preprocess-{index}"))
+                    .build()
+            })
+            .transpose()
+            .context("Failed to start preprocessing threads")?
+            .map(Arc::new),
+        dir_configs: !args.no_dir_config,
+        // Flags given on the command line win over `.catfinder.toml`
+        pinned_settings: PinnedSettings {
+            confidence: !is_default("confidence"),
+            classes: !is_default("class") || args.any_animal,
+            mask: args.mask.is_some(),
+        },
+        min_interval: args.min_interval,
+        io_timeout: args.io_timeout,
+        skip_screenshots: args.skip_screenshots,
+        skip_hashes: args.skip_hashes.as_deref().map(read_hash_list).transpose()?.map(Arc::new),
+        thumbnail_prefilter: args.thumbnail_prefilter.then_some(args.prefilter_confidence),
+        max_inferences: args.max_inferences,
+        probe_color: args.probe_color,
+        ..ScanOptions::default()
+    };
+
+    if let Some(manifest) = &args.eval {
+        return run_eval(detector.as_ref(), manifest, &options, &args);
+    }
+
+    if let Some(second_model) = &args.compare_model {
+        let second = YoloCatDetector::new(second_model, config)
+            .with_context(|| format!("Failed to load comparison model: {}", second_model.display()))?;
+        return run_compare(detector.as_ref(), &second, second_model, &options, &args);
+    }
+
+    if args.watch {
+        return run_watch(detector.as_ref(), &options, &args);
+    }
+
+    if let Some(paths) = &rescan_paths {
+        for missing in paths.iter().filter(|path| !path.exists()) {
+            diag!("Warning: {} no longer exists", missing.display());
+        }
+    }
+    let roots = rescan_paths.unwrap_or_else(|| vec![args.path.clone()]);
+    let source: Box<dyn ImageSource> = Box::new(WalkSource::new(roots, &options));
+
+    if args.estimate {
+        let total = count_image_files(source.as_ref());
+        run_estimate(detector.scan_source(source.as_ref(), options), total);
+        return Ok(());
+    }
+
+    let mut found_count = 0;
+    let mut total_count = 0;
+    let mut error_count = 0;
+    let mut oversized_count = 0;
+    // --report-unsupported: each file and what its decoder lacks
+    let mut unsupported: Vec<(PathBuf, String)> = Vec::new();
+    let mut timeout_count = 0;
+    let mut duplicate_count = 0;
+    let mut crops_written = 0;
+    let mut crops_skipped = 0;
+    let mut tree_copied = 0;
+    let mut tree_unchanged = 0;
+    let mut extensions: BTreeMap<String, ExtensionCounts> = BTreeMap::new();
+    let mut non_srgb_count = 0;
+
+    let crop_writer = args
+        .crop_dir
+        .as_deref()
+        .map(|dir| CropWriter::new(dir, args.overwrite_crops, args.crop_square))
+        .transpose()?
+        .map(|writer| writer.with_format(crop_format(&args)));
+    let tree_copier = args
+        .copy_tree
+        .as_deref()
+        .map(|dest| TreeCopier::new(&args.path, dest, args.dry_run))
+        .transpose()?;
+    let mut html_report = args.html_report.as_ref().map(|_| HtmlReport::new());
+    let mut heatmap = args.heatmap.as_ref().map(|_| Heatmap::new());
+    let mut contact_sheet = args
+        .contact_sheet
+        .as_deref()
+        .map(|path| ContactSheet::new(path, args.contact_sheet_columns));
+    let mut bursts = args.bursts.then(|| BurstFinder::new(args.burst_distance));
+    let mut largest = args.report_largest.map(|count| Largest::new(count, args.by_pixels));
+    let mut coco = (args.format == OutputFormat::Coco).then(|| CocoDataset::new(&args.class));
+    let mut exec_hook = args.exec.as_deref().map(|command| ExecHook::new(command, args.exec_batch)).transpose()?;
+
+    let open_list = |path: &Option<PathBuf>| {
+        path.as_deref().map(|path| PathList::create(path, args.print0)).transpose()
+    };
+    let mut result_db = args.sqlite.as_deref().map(ResultDb::open).transpose()?;
+    let cache_settings = if result_db.is_some() { scan_cache_settings(&args, detector.config(), &options)? } else { String::new() };
+    if args.skip_known
+        && let Some(db) = &result_db
+    {
+        options.known_files = Some(Arc::new(db.known_files(&cache_settings)?));
+    }
+    let mut found_list = open_list(&args.found_list)?;
+    let mut not_found_list = open_list(&args.not_found_list)?;
+    let mut error_list = open_list(&args.error_list)?;
+    let mut analysis_csv = args.analysis_csv.as_deref().map(AnalysisCsv::create).transpose()?;
+
+    // With --dedupe, matches are printed grouped by content after the scan.
+    // First path of each content group -> index into content_groups
+    let mut group_index: HashMap<PathBuf, usize> = HashMap::new();
+    let mut content_groups: Vec<Vec<ScanResult>> = Vec::new();
+    let mut match_dirs: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut printed = args.dedupe_output.then(PrintedPaths::default);
+    // Images in --maybe-range, printed after the hits
+    let mut maybes: Vec<ScanResult> = Vec::new();
+
+    let color = use_color(&args);
+    // Counted up front so --heartbeat can report the total and an ETA
+    let heartbeat_total = args.heartbeat.is_some().then(|| count_image_files(source.as_ref()));
+    let mut scan = detector.scan_source(source.as_ref(), options);
+
+    let mut heartbeat = args
+        .heartbeat
+        .as_deref()
+        .zip(heartbeat_total)
+        .map(|(path, total)| Heartbeat::new(path, Duration::from_secs(args.heartbeat_interval), total));
+
+    while args.max_errors.is_none_or(|max| error_count <= max)
+        && !output_closed()
+        && let Some(mut result) = scan.next()
+    {
+        let processed = total_count + scan.skipped(SkipReason::Known);
+        if let Some(heartbeat) = &mut heartbeat
+            && let Err(e) = heartbeat.tick(processed, found_count, error_count)
+        {
+            diag!("Warning: {e:#}");
+        }
+
+        total_count += 1;
+        extensions.entry(extension_key(&result.path)).or_default().processed += 1;
+
+        if let Some(e) = &result.error {
+            if let Some(list) = &mut error_list {
+                list.add(&result.path)?;
+            }
+
+            if e.is_too_large() {
+                oversized_count += 1;
+                if args.verbose {
+                    diag!(
+                        "Analyzing: {} ... {}: {}",
+                        result.path.display(),
+                        paint("SKIPPED", YELLOW, color),
+                        error_chain(e)
+                    );
+                }
+            } else {
+                // Timeouts count toward --max-errors, so a stalled share still aborts the scan
+                error_count += 1;
+                let label = if e.is_timeout() {
+                    timeout_count += 1;
+                    "TIMEOUT"
+                } else if args.report_unsupported && e.is_unsupported() {
+                    let detail = std::error::Error::source(e).map_or_else(|| e.to_string(), ToString::to_string);
+                    unsupported.push((result.path.clone(), detail));
+                    "UNSUPPORTED"
+                } else {
+                    "ERROR"
+                };
+                if args.verbose {
+                    diag!(
+                        "Analyzing: {} ... {}: {}",
+                        result.path.display(),
+                        paint(label, RED, color),
+                        error_chain(e)
+                    );
+                }
+            }
+            continue;
+        }
+
+        if let Some(profile) = &result.color_profile
+            && !profile.is_srgb()
+        {
+            non_srgb_count += 1;
+            diag!(
+                "Warning: {} has a non-sRGB color profile ({}); detection sees its colors unconverted",
+                result.path.display(),
+                profile.name()
+            );
+        }
+
+        // The detector ran down to the bottom of --maybe-range; only detections
+        // above --confidence count from here on
+        let mut borderline = Vec::new();
+        if let Some((low, high)) = args.maybe_range {
+            (result.detections, borderline) = std::mem::take(&mut result.detections)
+                .into_iter()
+                .partition(|d| d.confidence > args.confidence);
+            let best = best_confidence(&borderline);
+            if !result.detections.is_empty() || best <= low || best > high {
+                borderline.clear();
+            }
+        }
+
+        let is_match = is_match(&result, args.min_cats);
+
+        if args.verbose {
+            let mut status = describe_result(&result, is_match, &args.class);
+            if !borderline.is_empty() {
+                status.push_str(&format!(" (maybe: {:.2})", best_confidence(&borderline)));
+            }
+            let status = if is_match { paint(&status, GREEN, color) } else { status };
+            diag!("Analyzing: {} ... {}", result.path.display(), status);
+        }
+
+        if let Some(original) = &result.duplicate_of {
+            duplicate_count += 1;
+            if args.explain_skips {
+                diag!(
+                    "Skipped inference: {} (already-in-cache: same content as {})",
+                    result.path.display(),
+                    original.display()
+                );
+            }
+        }
+
+        // Before the sidecar, which carries the hash like the printed record
+        if (is_match || args.sidecar_all)
+            && let Err(e) = hash_for_output(&mut result, &args)
+        {
+            error_count += 1;
+            if args.verbose {
+                diag!("ERROR hashing {}: {}", result.path.display(), error_chain(&e));
+            }
+        }
+
+        if let Some(db) = &mut result_db {
+            if !result.detections.is_empty() && result.sha256.is_none() {
+                result.sha256 = calculate_sha256_with_buffer(&result.path, args.hash_buffer_kb as usize * 1024).ok();
+            }
+            let mtime = modified_nanos(&result.path);
+            let sha256 = result.sha256.as_deref();
+            if let Err(e) = db.record(&result.path, mtime, &cache_settings, sha256, &result.detections) {
+                error_count += 1;
+                if args.verbose {
+                    diag!("ERROR recording {} in SQLite: {}", result.path.display(), error_chain(&e));
+                }
+            }
+        }
+
+        if let Some(format) = args.sidecar
+            && (is_match || args.sidecar_all)
+            && let Err(e) = write_sidecar(&result.path, &image_record(&result, &args), format.into())
+        {
+            error_count += 1;
+            if args.verbose {
+                diag!("ERROR writing sidecar for {}: {}", result.path.display(), error_chain(&e));
+            }
+        }
+
+        if let Some(heatmap) = &mut heatmap
+            && let Some((width, height)) = result.dimensions
+        {
+            heatmap.add(width, height, &result.detections);
+        }
+
+        if let Some(csv) = &mut analysis_csv {
+            csv.add(&result)?;
+        }
+
+        if !is_match {
+            if let Some(list) = &mut not_found_list {
+                list.add(&result.path)?;
+            }
+            if !borderline.is_empty() {
+                result.detections = borderline;
+                maybes.push(result);
+            }
+            continue;
+        }
+
+        found_count += 1;
+        extensions.entry(extension_key(&result.path)).or_default().found += 1;
+
+        if let Some(list) = &mut found_list {
+            list.add(&result.path)?;
+        }
+
+        if let Some(coco) = &mut coco {
+            // COCO images need their size; a 0x0 entry would break training tools
+            match result.dimensions.map_or_else(|| image::image_dimensions(&result.path), Ok) {
+                Ok((width, height)) => coco.add(&result.path, width, height, &result.detections),
+                Err(e) => {
+                    error_count += 1;
+                    if args.verbose {
+                        diag!("ERROR adding {} to the COCO dataset: {}", result.path.display(), error_chain(&e));
+                    }
+                }
+            }
+        }
+
+        // A duplicate would take a second place with the same image
+        if let Some(largest) = &mut largest
+            && result.duplicate_of.is_none()
+        {
+            largest.add(&result);
+        }
+
+        if let Some(writer) = &crop_writer {
+            match writer.write_crops(&result) {
+                Ok(stats) => {
+                    crops_written += stats.written;
+                    crops_skipped += stats.skipped;
+                }
+                Err(e) => {
+                    error_count += 1;
+                    if args.verbose {
+                        diag!("ERROR writing crops for {}: {}", result.path.display(), error_chain(&e));
+                    }
+                }
+            }
+        }
+
+        if let Some(copier) = &tree_copier {
+            match copier.copy(&result.path) {
+                Ok(CopyOutcome::Copied(target)) => {
+                    tree_copied += 1;
+                    if args.dry_run {
+                        diag!("Would copy: {} -> {}", result.path.display(), target.display());
+                    }
+                }
+                Ok(CopyOutcome::Unchanged(_)) => tree_unchanged += 1,
+                Err(e) => {
+                    error_count += 1;
+                    if args.verbose {
+                        diag!("ERROR copying {}: {}", result.path.display(), error_chain(&e));
+                    }
+                }
+            }
+        }
+
+        if let Some(hook) = &mut exec_hook
+            && let Some(status) = hook.add(&result.path)?
+        {
+            error_count += 1;
+            if args.verbose {
+                let target = if args.exec_batch { "a batch of matches".into() } else { result.path.display().to_string() };
+                diag!("ERROR running --exec on {target}: {status}");
+            }
+        }
+
+        if let Some(bursts) = &mut bursts
+            && let Err(e) = bursts.add(&result)
+        {
+            error_count += 1;
+            if args.verbose {
+                diag!("ERROR hashing cat in {}: {}", result.path.display(), error_chain(&e));
+            }
+        }
+
+        if let Some(sheet) = &mut contact_sheet
+            && let Err(e) = sheet.add(&result)
+        {
+            error_count += 1;
+            if args.verbose {
+                diag!("ERROR adding {} to contact sheet: {}", result.path.display(), error_chain(&e));
+            }
+        }
+
+        if let Some(report) = &mut html_report
+            && let Err(e) = report.add(&result)
+        {
+            error_count += 1;
+            if args.verbose {
+                diag!("ERROR adding {} to HTML report: {}", result.path.display(), error_chain(&e));
+            }
+        }
+
+        if args.match_dirs {
+            let parent = result.path.parent().filter(|parent| !parent.as_os_str().is_empty());
+            match_dirs.insert(parent.unwrap_or(Path::new(".")).to_path_buf());
+        } else if args.dedupe {
+            let first = result.duplicate_of.clone().unwrap_or_else(|| result.path.clone());
+            let index = *group_index.entry(first).or_insert_with(|| {
+                content_groups.push(Vec::new());
+                content_groups.len() - 1
+            });
+            content_groups[index].push(result);
+        } else if printed.as_mut().is_none_or(|printed| printed.first(&result.path)) {
+            print_match(&result, &args);
+        }
+    }
+
+    let mut is_new = |result: &ScanResult| printed.as_mut().is_none_or(|printed| printed.first(&result.path));
+
+    for result in content_groups.iter().flatten().filter(|result| is_new(result)) {
+        print_match(result, &args);
+    }
+
+    for dir in &match_dirs {
+        emit(&dir.display().to_string(), args.print0);
+    }
+
+    for result in maybes.iter().filter(|result| is_new(result)) {
+        print_maybe(result, &args);
+    }
+
+    if let Some(coco) = &coco {
+        emit(&coco.to_json(args.json_pretty), false);
+    }
+
+    if let Some(hook) = &mut exec_hook
+        && let Some(status) = hook.flush()?
+    {
+        error_count += 1;
+        if args.verbose {
+            diag!("ERROR running --exec on the last batch: {status}");
+        }
+    }
+
+    for list in [found_list, not_found_list, error_list].into_iter().flatten() {
+        list.finish()?;
+    }
+
+    if let Some(csv) = analysis_csv {
+        csv.finish()?;
+    }
+
+    if let Some(db) = result_db {
+        db.finish()?;
+    }
+
+    if let Some(bursts) = &bursts {
+        let groups = bursts.groups();
+        diag!("Burst groups: {}", groups.len());
+        for (index, group) in groups.iter().enumerate() {
+            diag!("  Group {} ({} photos):", index + 1, group.len());
+            for path in group {
+                diag!("    {}", path.display());
+            }
+        }
+    }
+
+    if let Some(largest) = largest {
+        largest.report(&args);
+    }
+
+    if args.report_unsupported && !unsupported.is_empty() {
+        diag!("Unsupported files (transcode these to scan them): {}", unsupported.len());
+        for (path, detail) in &unsupported {
+            diag!("  {}: {detail}", path.display());
+        }
+    }
+
+    let processed = total_count + scan.skipped(SkipReason::Known);
+    if let Some(heartbeat) = &mut heartbeat
+        && let Err(e) = heartbeat.write(processed, found_count, error_count)
+    {
+        diag!("Warning: {e:#}");
+    }
+
+    if let (Some(report), Some(report_path)) = (&html_report, &args.html_report) {
+        report.write(report_path)?;
+        if args.verbose {
+            diag!("HTML report with {} images written to {}", report.len(), report_path.display());
+        }
+    }
+
+    if let Some(sheet) = contact_sheet {
+        let crops = sheet.len();
+        let sheets = sheet.finish()?;
+        if sheets.is_empty() {
+            diag!("No cat crops; contact sheet not written");
+        } else if args.verbose {
+            let names: Vec<String> = sheets.iter().map(|path| path.display().to_string()).collect();
+            diag!("Contact sheet of {crops} crops written to {}", names.join(", "));
+        }
+    }
+
+    if let (Some(heatmap), Some(heatmap_path)) = (&heatmap, &args.heatmap) {
+        heatmap.write(heatmap_path)?;
+        if let Some((width, height)) = heatmap.dimensions()
+            && heatmap.ignored() > 0
+        {
+            diag!(
+                "Warning: {} images are not {width}x{height} and were left out of the heatmap",
+                heatmap.ignored()
+            );
+        }
+        if args.verbose {
+            diag!("Heatmap of {} detections written to {}", heatmap.len(), heatmap_path.display());
+        }
+    }
+
+    // Reported as the error main returns, once the outputs are finished
+    let aborted = args.max_errors.filter(|&max| error_count > max);
+    if aborted.is_none() {
+        if scan.inference_limit_reached() {
+            diag!("Stopped after {} inference calls (--max-inferences); the scan is incomplete", scan.inferences());
+        } else if output_closed() && args.verbose {
+            diag!("Stopped early: the output pipe was closed");
+        }
+    }
+
+    if args.verbose || aborted.is_some() {
+        diag!();
+        diag!("Summary:");
+        diag!("  Files encountered: {}", scan.encountered());
+        // The walk leaves these out before counting, so name what was never seen
+        let unlisted: Vec<&str> = [
+            (args.shard.is_some(), "outside --shard"),
+            (args.min_depth > 0, "above --min-depth"),
+            (!args.include_hidden, "hidden"),
+        ]
+        .into_iter()
+        .filter_map(|(applies, what)| applies.then_some(what))
+        .collect();
+        if !unlisted.is_empty() {
+            diag!("    (not counting files {})", unlisted.join(" or "));
+        }
+        diag!("  Skipped (not an image): {}", scan.skipped(SkipReason::NotAnImage));
+        diag!("  Skipped (not a regular file): {}", scan.skipped(SkipReason::NotAFile));
+        diag!("  Skipped (unreadable): {}", scan.skipped(SkipReason::Unreadable));
+        let filtered: usize = SkipReason::ALL
+            .into_iter()
+            .filter(|reason| reason.is_filter())
+            .map(|reason| scan.skipped(reason))
+            .sum();
+        diag!("  Skipped by filters: {filtered}");
+        diag!("  Total images scanned: {total_count}");
+        diag!("  Images with cats: {found_count}");
+        if extensions.len() > 1 {
+            for (extension, counts) in &extensions {
+                diag!("    .{extension}: {} of {} scanned", counts.found, counts.processed);
+            }
+        }
+        if args.maybe_range.is_some() {
+            diag!("  Maybe (in --maybe-range): {}", maybes.len());
+        }
+        if args.dedupe {
+            diag!("  Duplicates reused: {duplicate_count}");
+        }
+        if let Some(printed) = &printed {
+            diag!("  Duplicate outputs suppressed: {}", printed.suppressed);
+        }
+        if crop_writer.is_some() {
+            diag!("  Crops written: {crops_written}");
+            diag!("  Crops already present: {crops_skipped}");
+        }
+        if tree_copier.is_some() {
+            let verb = if args.dry_run { "to copy" } else { "copied" };
+            diag!("  Images {verb} to --copy-tree: {tree_copied}");
+            diag!("  Images already in --copy-tree: {tree_unchanged}");
+        }
+        if args.skip_known {
+            diag!("  Skipped (unchanged in database): {}", scan.skipped(SkipReason::Known));
+        }
+        if args.thumbnail_prefilter {
+            diag!("  Ruled out by EXIF thumbnail: {}", scan.prefiltered());
+        }
+        if args.skip_screenshots {
+            diag!("  Skipped (screenshots): {}", scan.skipped(SkipReason::Screenshot));
+        }
+        if args.skip_hashes.is_some() {
+            diag!("  Skipped (hash in --skip-hashes): {}", scan.skipped(SkipReason::KnownHash));
+        }
+        if args.probe_color {
+            diag!("  Non-sRGB color profiles: {non_srgb_count}");
+        }
+        if args.max_inferences.is_some() {
+            diag!("  Inference calls: {}", scan.inferences());
+        }
+        if oversized_count > 0 {
+            diag!("  Skipped (over --max-pixels): {oversized_count}");
+        }
+        if error_count > 0 {
+            diag!("  Errors: {error_count}");
+        }
+        if timeout_count > 0 {
+            diag!("  Of which timed out (over --io-timeout): {timeout_count}");
+        }
+        if args.report_unsupported {
+            diag!("  Of which unsupported (codec not available): {}", unsupported.len());
+        }
+    }
+
+    if let Some(path) = &args.stats {
+        let stats = ScanStats {
+            encountered: scan.encountered(),
+            skipped: SkipReason::ALL
+                .into_iter()
+                .map(|reason| (reason.to_string(), scan.skipped(reason)))
+                .collect(),
+            processed: total_count,
+            found: found_count,
+            maybe: args.maybe_range.map(|_| maybes.len()),
+            errors: error_count,
+            oversized: oversized_count,
+            timed_out: timeout_count,
+            duplicates: duplicate_count,
+            duplicate_outputs: printed.as_ref().map(|printed| printed.suppressed),
+            prefiltered: scan.prefiltered(),
+            inferences: scan.inferences(),
+            extensions,
+            complete: aborted.is_none() && !scan.inference_limit_reached() && !output_closed(),
+        };
+        stats.write(path)?;
+    }
+
+    if let Some(path) = &args.trace {
+        write_trace(path).with_context(|| format!("Failed to write trace to {}", path.display()))?;
+        if args.verbose {
+            diag!("Trace of {} spans written to {}", trace_len(), path.display());
+        }
+    }
+
+    if let Some(max) = aborted {
+        anyhow::bail!("Aborted after more than {max} errors (--max-errors)");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_sidecar_includes_sha256_with_hash() {
+        let dir = std::env::temp_dir().join(format!("cat-finder-sidecar-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let image = dir.join("cat.jpg");
+        fs::copy(Path::new(env!("CARGO_MANIFEST_DIR")).join("samples/cat_tuxedo_medium.jpg"), &image).unwrap();
+
+        let args = Args::parse_from(["cat-finder", "--sidecar", "json", "--with-hash"]);
+        let mut result = ScanResult {
+            path: image.clone(),
+            detections: Vec::new(),
+            timestamp: None,
+            sha256: None,
+            duplicate_of: None,
+            rotation: 0,
+            scales: Vec::new(),
+            dimensions: None,
+            color_profile: None,
+            error: None,
+        };
+        // The order the scan loop writes a sidecar in
+        hash_for_output(&mut result, &args).unwrap();
+        let sidecar = write_sidecar(&image, &image_record(&result, &args), SidecarFormat::Json).unwrap();
+
+        let record: serde_json::Value = serde_json::from_str(&fs::read_to_string(&sidecar).unwrap()).unwrap();
+        let expected = calculate_sha256(&image).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(record["sha256"], expected.as_str());
+    }
 }
@@ -0,0 +1,63 @@
+use crate::detector::Detection;
+use crate::error::{DetectError, Result};
+use image::GrayImage;
+use std::path::Path;
+
+/// Aspect ratios further apart than this factor trigger a warning
+const ASPECT_TOLERANCE: f32 = 1.25;
+
+/// Irregular ignore zones: black pixels in a mask image.
+///
+/// The mask is stretched over each scanned image, so one mask drawn at any
+/// resolution serves every frame from the same camera. A detection is dropped
+/// when the center of its box lands on a masked pixel.
+#[derive(Clone, Debug)]
+pub struct IgnoreMask {
+    mask: GrayImage,
+}
+
+impl IgnoreMask {
+    pub fn load(path: &Path) -> Result<Self> {
+        let mask = image::open(path).map_err(|e| DetectError::image(path, e))?.to_luma8();
+
+        Ok(Self { mask })
+    }
+
+    /// Whether the point `(x, y)` of a `width`x`height` image falls on a masked pixel
+    pub fn is_masked(&self, x: f32, y: f32, width: u32, height: u32) -> bool {
+        if width == 0 || height == 0 {
+            return false;
+        }
+
+        let mx = (x / width as f32 * self.mask.width() as f32) as u32;
+        let my = (y / height as f32 * self.mask.height() as f32) as u32;
+        let mx = mx.min(self.mask.width().saturating_sub(1));
+        let my = my.min(self.mask.height().saturating_sub(1));
+
+        self.mask.get_pixel(mx, my).0[0] < 128
+    }
+
+    /// Drop detections whose box center is masked
+    pub fn retain_unmasked(&self, detections: &mut Vec<Detection>, width: u32, height: u32) {
+        detections.retain(|d| {
+            let (cx, cy) = d.bbox.center();
+            !self.is_masked(cx, cy, width, height)
+        });
+    }
+
+    /// Whether stretching the mask over a `width`x`height` image distorts it noticeably
+    pub fn aspect_mismatch(&self, width: u32, height: u32) -> bool {
+        if width == 0 || height == 0 || self.mask.width() == 0 || self.mask.height() == 0 {
+            return false;
+        }
+
+        let image_aspect = width as f32 / height as f32;
+        let mask_aspect = self.mask.width() as f32 / self.mask.height() as f32;
+        let ratio = image_aspect.max(mask_aspect) / image_aspect.min(mask_aspect);
+        ratio > ASPECT_TOLERANCE
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.mask.dimensions()
+    }
+}
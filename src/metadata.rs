@@ -0,0 +1,97 @@
+use exif::{Exif, In, Tag, Value};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Camera, lens and location from a photo's EXIF tags. Fields the photo
+/// does not record are `None` and left out of JSON.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhotoMetadata {
+    /// Camera manufacturer (`Make`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub make: Option<String>,
+    /// Camera model (`Model`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Lens model (`LensModel`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lens: Option<String>,
+    /// Latitude in decimal degrees, negative south of the equator
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    /// Longitude in decimal degrees, negative west of Greenwich
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+}
+
+impl PhotoMetadata {
+    /// Read the EXIF tags of the image at `path`; a file without EXIF (or
+    /// one that cannot be read) gives empty metadata
+    pub fn read(path: &Path) -> Self {
+        let Ok(file) = File::open(path) else {
+            return Self::default();
+        };
+        let Ok(exif) = exif::Reader::new().read_from_container(&mut BufReader::new(file)) else {
+            return Self::default();
+        };
+
+        // A position needs both coordinates
+        let (latitude, longitude) = match (
+            coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S'),
+            coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W'),
+        ) {
+            (Some(latitude), Some(longitude)) => (Some(latitude), Some(longitude)),
+            _ => (None, None),
+        };
+
+        Self {
+            make: text(&exif, Tag::Make),
+            model: text(&exif, Tag::Model),
+            lens: text(&exif, Tag::LensModel),
+            latitude,
+            longitude,
+        }
+    }
+
+    /// Whether no field was found
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether the photo names the camera that took it
+    pub fn has_camera(&self) -> bool {
+        self.make.is_some() || self.model.is_some()
+    }
+}
+
+/// An ASCII tag, trimmed; blank values count as missing
+fn text(exif: &Exif, tag: Tag) -> Option<String> {
+    let Value::Ascii(values) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+
+    let text = String::from_utf8_lossy(values.first()?);
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// A GPS coordinate stored as degrees, minutes and seconds, negated when
+/// its reference tag is `negative` (`S` or `W`)
+fn coordinate(exif: &Exif, tag: Tag, reference: Tag, negative: u8) -> Option<f64> {
+    let Value::Rational(parts) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+
+    let [degrees, minutes, seconds] = [0, 1, 2].map(|i| parts.get(i).map_or(0.0, |part| part.to_f64()));
+    let value = degrees + minutes / 60.0 + seconds / 3600.0;
+    if !value.is_finite() {
+        return None;
+    }
+
+    let negated = matches!(
+        &exif.get_field(reference, In::PRIMARY).map(|field| &field.value),
+        Some(Value::Ascii(values)) if values.first().and_then(|v| v.first()) == Some(&negative)
+    );
+    Some(if negated { -value } else { value })
+}
@@ -0,0 +1,83 @@
+use crate::detector::Detection;
+use crate::error::{DetectError, Result};
+use crate::metadata::PhotoMetadata;
+use crate::scan::ScaleResult;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Serializable form of one matched image, as written by `--format json`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ImageRecord {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Camera, lens and GPS position from EXIF, for `--with-exif`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exif: Option<PhotoMetadata>,
+    /// Clockwise rotation in degrees at which the cat was found
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub rotation: u32,
+    pub detections: Vec<DetectionRecord>,
+    /// Best confidence per input size, for multi-scale runs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scales: Vec<ScaleResult>,
+    /// Best confidence is within `--maybe-range` but below the threshold
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub maybe: bool,
+}
+
+/// Serializable form of a [`Detection`]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DetectionRecord {
+    pub class: String,
+    pub class_id: usize,
+    pub confidence: f32,
+    /// `[x1, y1, x2, y2]` in original image pixels
+    pub bbox: [f32; 4],
+}
+
+impl From<&Detection> for DetectionRecord {
+    fn from(detection: &Detection) -> Self {
+        let b = detection.bbox;
+        Self {
+            class: detection.class_name().to_string(),
+            class_id: detection.class_id,
+            confidence: detection.confidence,
+            bbox: [b.x1, b.y1, b.x2, b.y2],
+        }
+    }
+}
+
+/// Read the records of a `--format json` output, one JSON object per line
+/// (blank lines are skipped)
+pub fn read_records(path: &Path) -> Result<Vec<ImageRecord>> {
+    let contents = fs::read_to_string(path).map_err(DetectError::io("failed to read results", path))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| DetectError::InvalidInput {
+                path: path.to_path_buf(),
+                message: format!("line {}: {e}", index + 1),
+            })
+        })
+        .collect()
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
@@ -0,0 +1,119 @@
+use crate::detector::{BoundingBox, CatDetector, Detection, DetectorConfig, ModelInput, merge_nearby, non_max_suppression};
+use crate::error::{DetectError, Result};
+use crate::trace::span;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Longest wait for the server to answer one image
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Runs inference on an HTTP server instead of a local ONNX session.
+///
+/// Each [`ModelInput`] is POSTed to the endpoint as its raw tensor:
+/// little-endian `f32` values in NCHW order (or the configured
+/// [`crate::TensorLayout`]), with the shape in an
+/// `X-Input-Shape` header (e.g. `1,3,640,640`). The server answers with
+///
+/// ```json
+/// {"detections": [{"class_id": 15, "confidence": 0.91, "box": [x1, y1, x2, y2]}]}
+/// ```
+///
+/// where boxes are in input tensor pixels. Thresholds, class selection and
+/// non-maximum suppression are applied here, so the server may return every
+/// candidate it finds.
+pub struct RemoteDetector {
+    url: String,
+    agent: ureq::Agent,
+    config: DetectorConfig,
+}
+
+#[derive(Deserialize)]
+struct RemoteResponse {
+    detections: Vec<RemoteDetection>,
+}
+
+#[derive(Deserialize)]
+struct RemoteDetection {
+    class_id: usize,
+    confidence: f32,
+    #[serde(rename = "box")]
+    bbox: [f32; 4],
+}
+
+impl RemoteDetector {
+    pub fn new(url: &str, config: DetectorConfig) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(REQUEST_TIMEOUT))
+            .build()
+            .into();
+
+        Self {
+            url: url.to_string(),
+            agent,
+            config,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl CatDetector for RemoteDetector {
+    fn config(&self) -> &DetectorConfig {
+        &self.config
+    }
+
+    fn detect_input_with(&self, input: &ModelInput, config: &DetectorConfig) -> Result<Vec<Detection>> {
+        let shape = input.tensor.shape().iter().map(usize::to_string).collect::<Vec<_>>().join(",");
+        let body: Vec<u8> = input.tensor.iter().flat_map(|value| value.to_le_bytes()).collect();
+
+        let remote_error = |source| DetectError::Remote {
+            context: format!("inference request to {} failed", self.url),
+            source,
+        };
+        let infer_span = span("infer", None);
+        let response: RemoteResponse = self
+            .agent
+            .post(&self.url)
+            .header("Content-Type", "application/octet-stream")
+            .header("X-Input-Shape", &shape)
+            .send(&body[..])
+            .and_then(|mut response| response.body_mut().read_json())
+            .map_err(remote_error)?;
+        drop(infer_span);
+
+        // Boxes are predicted in model input space; map them back to the original image
+        let scale_x = input.orig_width as f32 / input.input_size as f32;
+        let scale_y = input.orig_height as f32 / input.input_size as f32;
+        let threshold = config.confidence_for(input.orig_width, input.orig_height);
+
+        let detections = response
+            .detections
+            .into_iter()
+            .filter_map(|d| {
+                let confidence = config.calibrate(d.confidence);
+                if confidence <= threshold {
+                    return None;
+                }
+                let [x1, y1, x2, y2] = d.bbox;
+                Some(Detection {
+                    class_id: config.report_class(d.class_id)?,
+                    confidence,
+                    bbox: BoundingBox {
+                        x1: x1 * scale_x,
+                        y1: y1 * scale_y,
+                        x2: x2 * scale_x,
+                        y2: y2 * scale_y,
+                    },
+                })
+            })
+            .collect();
+
+        let detections = non_max_suppression(detections, config.iou_threshold);
+        Ok(match config.merge_distance {
+            Some(distance) => merge_nearby(detections, distance),
+            None => detections,
+        })
+    }
+}
@@ -0,0 +1,1188 @@
+use crate::decode::{
+    MAX_ANIMATION_FRAMES, exif_thumbnail, is_animated_format, is_jpeg, open_frames, open_image, open_jpeg_scaled,
+};
+use crate::dirconfig::{DirConfigs, DirSettings, PinnedSettings};
+use crate::detector::{
+    CAT_CLASS_ID, CatDetector, Detection, DetectorConfig, INPUT_SIZE, ModelInput, YOLO_CLASSES,
+    YoloCatDetector, detect_rotations_from, non_max_suppression,
+};
+use crate::error::{DetectError, Result};
+use crate::hash::{DEFAULT_HASH_BUFFER_SIZE, calculate_sha256_with_buffer};
+use crate::icc::{ColorProfile, read_color_profile};
+use crate::mask::IgnoreMask;
+use crate::metadata::PhotoMetadata;
+use crate::screenshot::screenshot_hint_sized;
+use crate::source::{ImageSource, Shard, WalkSource};
+use crate::trace::span;
+use chrono::{DateTime, Local};
+use image::{DynamicImage, GenericImageView};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+
+/// Order in which directory entries are visited
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Lexicographic by file name
+    Name,
+    /// Oldest modification time first, then by name
+    Mtime,
+    /// Smallest file first, then by name
+    Size,
+}
+
+/// How per-frame results of an animation combine into one verdict
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameAggregation {
+    /// Any frame with a cat flags the file
+    Any,
+    /// More than half of the frames must contain a cat
+    Majority,
+    /// The mean of each frame's best cat confidence (0 for frames without
+    /// one) must reach the detection threshold
+    Mean,
+}
+
+/// Outcome of one input size in a multi-scale run (see [`ScanOptions::scales`])
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScaleResult {
+    pub input_size: u32,
+    /// Highest detection confidence at this size, if anything was found
+    pub best_confidence: Option<f32>,
+}
+
+/// Why [`Scan`] passed over a directory entry without analyzing it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SkipReason {
+    /// Extension is not a supported image format
+    NotAnImage,
+    /// Not a regular file (socket, device, broken symlink, ...)
+    NotAFile,
+    /// The entry could not be read while walking
+    Unreadable,
+    /// Unchanged since it was last scanned (see [`ScanOptions::known_files`])
+    Known,
+    /// Looks like a screenshot or meme (see [`ScanOptions::skip_screenshots`])
+    Screenshot,
+    /// Contents already confirmed to show no cat (see [`ScanOptions::skip_hashes`])
+    KnownHash,
+}
+
+impl SkipReason {
+    /// Every reason, in the order summaries list them
+    pub const ALL: [SkipReason; 6] = [
+        SkipReason::NotAnImage,
+        SkipReason::NotAFile,
+        SkipReason::Unreadable,
+        SkipReason::Known,
+        SkipReason::Screenshot,
+        SkipReason::KnownHash,
+    ];
+
+    /// Whether the entry was an image passed over by a scan option rather
+    /// than something that could never be analyzed
+    pub fn is_filter(self) -> bool {
+        matches!(self, SkipReason::Known | SkipReason::Screenshot | SkipReason::KnownHash)
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::NotAnImage => "not-an-image",
+            SkipReason::NotAFile => "not-a-file",
+            SkipReason::Unreadable => "unreadable",
+            SkipReason::Known => "unchanged-since-last-scan",
+            SkipReason::Screenshot => "screenshot",
+            SkipReason::KnownHash => "known-no-cat-hash",
+        })
+    }
+}
+
+/// Options controlling how [`YoloCatDetector::scan`] walks and analyzes a directory
+#[derive(Clone, Debug)]
+pub struct ScanOptions {
+    /// Follow symbolic links while walking
+    pub follow_links: bool,
+    /// Skip entries fewer than this many levels below the root (0: the root
+    /// itself, 1: its direct children)
+    pub min_depth: usize,
+    /// Hash each file and reuse the result for byte-identical copies
+    pub dedupe: bool,
+    /// Retry detection on 90/180/270 degree rotations when the upright image has no cat
+    pub try_rotations: bool,
+    /// Test-time augmentation: also run detection on the horizontally
+    /// flipped image and merge both sets of boxes with NMS, for better
+    /// recall at twice the inference cost
+    pub tta: bool,
+    /// Visit siblings in a fixed order instead of filesystem order
+    pub sort: Option<SortOrder>,
+    /// Refuse to decode images with more pixels than this
+    pub max_pixels: Option<u64>,
+    /// Read size in bytes when hashing for [`ScanOptions::dedupe`]
+    pub hash_buffer_size: usize,
+    /// Analyze every frame of animated GIFs and combine them this way
+    /// (default: only the first frame is analyzed)
+    pub frame_agg: Option<FrameAggregation>,
+    /// Run inference at each of these input sizes and merge the detections
+    /// (empty: the single default [`crate::INPUT_SIZE`])
+    pub scales: Vec<u32>,
+    /// Skip files whose canonical path maps to their current modification
+    /// time (nanoseconds since the epoch, as from [`modified_nanos`])
+    pub known_files: Option<Arc<HashMap<PathBuf, i64>>>,
+    /// SHA-256 checksums (lowercase hex) of files known to show no cat:
+    /// each image is hashed and skipped without inference when its
+    /// checksum is listed (see [`crate::read_hash_list`])
+    pub skip_hashes: Option<Arc<HashSet<String>>>,
+    /// Print each skipped entry and its [`SkipReason`] to stderr
+    pub explain_skips: bool,
+    /// Discard detections centered on black pixels of this mask
+    pub mask: Option<Arc<IgnoreMask>>,
+    /// Decode and preprocess upcoming images on this pool while the current
+    /// one is in inference (default: everything runs on the calling thread)
+    pub preprocess_pool: Option<Arc<rayon::ThreadPool>>,
+    /// Apply the `.catfinder.toml` of each image's directory and its
+    /// ancestors up to the scan root (see [`crate::DIR_CONFIG_FILE`]). Off by
+    /// default.
+    pub dir_configs: bool,
+    /// Settings chosen explicitly, which [`ScanOptions::dir_configs`] files
+    /// don't override
+    pub pinned_settings: PinnedSettings,
+    /// Idle at least this long between the end of one image's inference and
+    /// the start of the next, capping sustained load
+    pub min_interval: Option<Duration>,
+    /// Give up on reading, hashing or decoding a file after this long,
+    /// reporting [`DetectError::Timeout`] (default: wait indefinitely)
+    pub io_timeout: Option<Duration>,
+    /// End the scan once this many inference calls have been made. Checked
+    /// before each image, so an image needing several calls (rotations,
+    /// scales, frames) can finish a few past the limit.
+    pub max_inferences: Option<usize>,
+    /// Pass over images that look like screenshots or memes rather than
+    /// photos (see [`crate::screenshot_hint`])
+    pub skip_screenshots: bool,
+    /// Run detection on the embedded EXIF thumbnail first, at this lower
+    /// confidence, and only analyze the full image when the thumbnail shows
+    /// something. Images without a thumbnail are always analyzed in full.
+    pub thumbnail_prefilter: Option<f32>,
+    /// Only visit the files of this part of the library (see [`Shard`])
+    pub shard: Option<Shard>,
+    /// Decode large JPEGs at a reduced DCT scale that still covers the model
+    /// input (see [`crate::open_jpeg_scaled`]). Faster, but confidences can
+    /// differ slightly from a full decode; off by default. Boxes and
+    /// dimensions are always reported at full size.
+    pub jpeg_dct_scaling: bool,
+    /// Leave out files and directories whose name starts with `.`, such as
+    /// `.thumbnails` or `.git` (see [`crate::is_hidden`]). Off by default;
+    /// the `cat-finder` command line turns it on.
+    pub skip_hidden: bool,
+    /// Read each image's embedded ICC profile into
+    /// [`ScanResult::color_profile`], along with the decode
+    pub probe_color: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            follow_links: true,
+            min_depth: 0,
+            dedupe: false,
+            try_rotations: false,
+            tta: false,
+            sort: None,
+            max_pixels: None,
+            hash_buffer_size: DEFAULT_HASH_BUFFER_SIZE,
+            explain_skips: false,
+            frame_agg: None,
+            known_files: None,
+            skip_hashes: None,
+            scales: Vec::new(),
+            mask: None,
+            preprocess_pool: None,
+            dir_configs: false,
+            pinned_settings: PinnedSettings::default(),
+            min_interval: None,
+            io_timeout: None,
+            skip_screenshots: false,
+            max_inferences: None,
+            thumbnail_prefilter: None,
+            shard: None,
+            jpeg_dct_scaling: false,
+            skip_hidden: false,
+            probe_color: false,
+        }
+    }
+}
+
+/// Outcome of analyzing a single image
+#[derive(Debug)]
+pub struct ScanResult {
+    pub path: PathBuf,
+    /// Cat detections (empty when none were found or an error occurred)
+    pub detections: Vec<Detection>,
+    /// File timestamp and its source (F for file-based, M for metadata-based)
+    pub timestamp: Option<(DateTime<Local>, char)>,
+    /// SHA-256 of the file contents (computed with [`ScanOptions::dedupe`])
+    pub sha256: Option<String>,
+    /// Earlier file in this scan with identical contents whose result was reused
+    pub duplicate_of: Option<PathBuf>,
+    /// Clockwise rotation in degrees at which the cat was found (0 = upright)
+    pub rotation: u32,
+    /// Per-size results when [`ScanOptions::scales`] is set
+    pub scales: Vec<ScaleResult>,
+    /// Width and height of the decoded image (`None` when it failed to load)
+    pub dimensions: Option<(u32, u32)>,
+    /// Embedded ICC profile under [`ScanOptions::probe_color`] (`None` when
+    /// the image has none or it could not be read)
+    pub color_profile: Option<ColorProfile>,
+    pub error: Option<DetectError>,
+}
+
+impl ScanResult {
+    /// Whether anything of the requested classes was detected (cats by default)
+    pub fn has_cats(&self) -> bool {
+        !self.detections.is_empty()
+    }
+
+    /// Number of distinct cats detected (after non-maximum suppression)
+    pub fn cat_count(&self) -> usize {
+        self.detections.iter().filter(|d| d.class_id == CAT_CLASS_ID).count()
+    }
+
+    /// Number of detections per class name, in class ID order
+    pub fn class_counts(&self) -> Vec<(&'static str, usize)> {
+        let mut counts = BTreeMap::new();
+        for detection in &self.detections {
+            *counts.entry(detection.class_id).or_insert(0) += 1;
+        }
+
+        counts
+            .into_iter()
+            .map(|(class_id, count)| (YOLO_CLASSES.get(class_id).copied().unwrap_or("unknown"), count))
+            .collect()
+    }
+}
+
+/// Lazy iterator over the images under a directory; see [`YoloCatDetector::scan`]
+pub struct Scan<'a> {
+    detector: CountingDetector<'a>,
+    entries: Box<dyn Iterator<Item = Result<PathBuf>>>,
+    options: ScanOptions,
+    // Content hash -> result of the first file with that content
+    seen_hashes: HashMap<String, CachedResult>,
+    skip_counts: HashMap<SkipReason, usize>,
+    // Entries taken from the source, skipped or not
+    encountered: usize,
+    // Images handed to the preprocessing pool, in walk order
+    pending: VecDeque<(PathBuf, mpsc::Receiver<Prepared>)>,
+    // Content hashes the preprocessing pool has decoded a file for
+    queued_hashes: Arc<Mutex<HashSet<String>>>,
+    dir_configs: DirConfigs,
+    // When the previous inference finished, for ScanOptions::min_interval
+    last_inference: Option<Instant>,
+    // Images settled by their thumbnail under ScanOptions::thumbnail_prefilter
+    prefiltered: usize,
+    // An image was refused at ScanOptions::max_inferences
+    inference_limit_hit: bool,
+}
+
+/// Passes inference through to a detector, counting the calls for
+/// [`ScanOptions::max_inferences`]
+struct CountingDetector<'a> {
+    inner: &'a dyn CatDetector,
+    calls: Cell<usize>,
+}
+
+impl CatDetector for CountingDetector<'_> {
+    fn config(&self) -> &DetectorConfig {
+        self.inner.config()
+    }
+
+    fn detect_input_with(&self, input: &ModelInput, config: &DetectorConfig) -> Result<Vec<Detection>> {
+        self.calls.set(self.calls.get() + 1);
+        self.inner.detect_input_with(input, config)
+    }
+}
+
+struct CachedResult {
+    path: PathBuf,
+    analysis: Analysis,
+    // Per-directory settings the result was found under
+    settings: Arc<DirSettings>,
+}
+
+/// A decoded frame and, when prepared ahead of inference, its model input
+/// for each size in [`ScanOptions::scales`] (or [`INPUT_SIZE`])
+struct Frame {
+    image: DynamicImage,
+    /// Size of the image in the file; larger than `image` when it was
+    /// decoded at a reduced JPEG scale
+    original: (u32, u32),
+    inputs: Vec<ModelInput>,
+}
+
+impl Frame {
+    fn new(image: DynamicImage) -> Self {
+        Self {
+            original: image.dimensions(),
+            image,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Model input at `size` whose boxes come out at the original size
+    fn input(&self, size: u32, config: &DetectorConfig) -> ModelInput {
+        let mut input = ModelInput::new(&self.image, size, config);
+        (input.orig_width, input.orig_height) = self.original;
+        input
+    }
+}
+
+/// One image as the preprocessing pool hands it back (see [`prepare`])
+#[derive(Default)]
+struct Prepared {
+    /// SHA-256 of the file, when the scan hashes
+    sha256: Option<Result<String>>,
+    /// The EXIF thumbnail under [`ScanOptions::thumbnail_prefilter`]; the
+    /// inner `None` when the file has none
+    thumbnail: Option<Option<Thumbnail>>,
+    /// Decoded frames with their model inputs; `None` when the hash showed
+    /// the image needs no decoding, or its thumbnail is checked first
+    frames: Option<Result<Decoded>>,
+}
+
+/// The frames of one image and what else the same bounded read learned
+/// about the file
+struct Decoded {
+    frames: Vec<Frame>,
+    /// The EXIF names a camera; only read under [`ScanOptions::skip_screenshots`]
+    camera: bool,
+    /// Only read under [`ScanOptions::probe_color`]
+    color_profile: Option<ColorProfile>,
+}
+
+/// An image's EXIF thumbnail and the size of the full image, read together
+/// for [`ScanOptions::thumbnail_prefilter`]
+struct Thumbnail {
+    image: DynamicImage,
+    /// `None` when the full image's header cannot be read
+    original: Option<(u32, u32)>,
+    /// Only read under [`ScanOptions::probe_color`]
+    color_profile: Option<ColorProfile>,
+}
+
+/// Everything detection produced for one image
+#[derive(Clone, Default)]
+struct Analysis {
+    detections: Vec<Detection>,
+    rotation: u32,
+    scales: Vec<ScaleResult>,
+    dimensions: Option<(u32, u32)>,
+    color_profile: Option<ColorProfile>,
+}
+
+impl Analysis {
+    fn top_confidence(&self) -> f32 {
+        self.detections.iter().map(|d| d.confidence).fold(0.0, f32::max)
+    }
+
+    fn apply_to(self, result: &mut ScanResult) {
+        result.detections = self.detections;
+        result.rotation = self.rotation;
+        result.scales = self.scales;
+        result.dimensions = self.dimensions;
+        result.color_profile = self.color_profile;
+    }
+}
+
+impl YoloCatDetector {
+    /// Walk `root` and analyze each image file lazily, one result per image
+    pub fn scan(&self, root: &Path, options: ScanOptions) -> Scan<'_> {
+        <dyn CatDetector>::scan(self, root, options)
+    }
+
+    /// Like [`YoloCatDetector::scan`], over several files or directories in
+    /// the given order (directories are walked as in `scan`)
+    pub fn scan_paths<'a, I>(&'a self, paths: I, options: ScanOptions) -> Scan<'a>
+    where
+        I: IntoIterator<Item = PathBuf>,
+        I::IntoIter: 'a,
+    {
+        <dyn CatDetector>::scan_paths(self, paths, options)
+    }
+
+    /// Analyze the files `source` lists, e.g. a [`crate::ListSource`]
+    pub fn scan_source(&self, source: &dyn ImageSource, options: ScanOptions) -> Scan<'_> {
+        <dyn CatDetector>::scan_source(self, source, options)
+    }
+}
+
+impl<'d> dyn CatDetector + 'd {
+    /// [`YoloCatDetector::scan`] with any detector, e.g. a [`crate::RemoteDetector`]
+    pub fn scan(&self, root: &Path, options: ScanOptions) -> Scan<'_> {
+        let source = WalkSource::new(vec![root.to_path_buf()], &options);
+        self.scan_source(&source, options)
+    }
+
+    /// [`YoloCatDetector::scan_paths`] with any detector
+    pub fn scan_paths<'a, I>(&'a self, paths: I, options: ScanOptions) -> Scan<'a>
+    where
+        I: IntoIterator<Item = PathBuf>,
+        I::IntoIter: 'a,
+    {
+        let source = WalkSource::new(paths.into_iter().collect(), &options);
+        self.scan_source(&source, options)
+    }
+
+    /// [`YoloCatDetector::scan_source`] with any detector
+    pub fn scan_source(&self, source: &dyn ImageSource, options: ScanOptions) -> Scan<'_> {
+        let dir_configs = DirConfigs::new(&source.roots(), options.pinned_settings);
+        Scan {
+            detector: CountingDetector {
+                inner: self,
+                calls: Cell::new(0),
+            },
+            entries: source.images(),
+            options,
+            seen_hashes: HashMap::new(),
+            skip_counts: HashMap::new(),
+            encountered: 0,
+            pending: VecDeque::new(),
+            queued_hashes: Arc::default(),
+            dir_configs,
+            last_inference: None,
+            prefiltered: 0,
+            inference_limit_hit: false,
+        }
+    }
+}
+
+impl Scan<'_> {
+    /// Number of entries passed over so far for `reason`
+    pub fn skipped(&self, reason: SkipReason) -> usize {
+        self.skip_counts.get(&reason).copied().unwrap_or(0)
+    }
+
+    /// Number of entries the source has listed so far, whether they were
+    /// analyzed, skipped or are still being decoded ahead. A [`WalkSource`]
+    /// never lists files outside [`ScanOptions::shard`], above
+    /// [`ScanOptions::min_depth`] or hidden under [`ScanOptions::skip_hidden`],
+    /// so those are not counted anywhere.
+    pub fn encountered(&self) -> usize {
+        self.encountered
+    }
+
+    /// Number of inference calls made so far (several per image with
+    /// rotations, multiple scales or animation frames)
+    pub fn inferences(&self) -> usize {
+        self.detector.calls.get()
+    }
+
+    /// Number of images ruled out by their EXIF thumbnail alone under
+    /// [`ScanOptions::thumbnail_prefilter`], without decoding the full image
+    pub fn prefiltered(&self) -> usize {
+        self.prefiltered
+    }
+
+    /// Whether the scan ended early at [`ScanOptions::max_inferences`], i.e.
+    /// an image was left unanalyzed. A scan whose last image brings the
+    /// count to exactly the limit is complete.
+    pub fn inference_limit_reached(&self) -> bool {
+        self.inference_limit_hit
+    }
+
+    /// Analyze one image, or `None` when it turns out to be skipped
+    fn analyze(&mut self, path: PathBuf, prepared: Option<Prepared>) -> Option<ScanResult> {
+        let Prepared { sha256, thumbnail, frames } = prepared.unwrap_or_default();
+        let mut result = ScanResult {
+            timestamp: get_image_timestamp(&path),
+            path,
+            detections: Vec::new(),
+            sha256: None,
+            duplicate_of: None,
+            rotation: 0,
+            scales: Vec::new(),
+            dimensions: None,
+            color_profile: None,
+            error: None,
+        };
+
+        let settings = if self.options.dir_configs {
+            match self.dir_configs.for_file(&result.path) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    result.error = Some(e);
+                    return Some(result);
+                }
+            }
+        } else {
+            Arc::default()
+        };
+
+        if needs_hash(&self.options) {
+            let sha256 = sha256.unwrap_or_else(|| hash_within(&result.path, &self.options));
+            match sha256 {
+                Ok(hash) => {
+                    if self.options.skip_hashes.as_ref().is_some_and(|hashes| hashes.contains(&hash)) {
+                        self.report_skip(&result.path, SkipReason::KnownHash, None);
+                        return None;
+                    }
+                    // Identical content was already analyzed this run under the
+                    // same per-directory settings: reuse its result
+                    if self.options.dedupe
+                        && let Some(cached) = self.seen_hashes.get(&hash)
+                        && cached.settings.same_as(&settings)
+                    {
+                        cached.analysis.clone().apply_to(&mut result);
+                        result.duplicate_of = Some(cached.path.clone());
+                        result.sha256 = Some(hash);
+                        return Some(result);
+                    }
+                    result.sha256 = Some(hash);
+                }
+                Err(e) => {
+                    result.error = Some(e);
+                    return Some(result);
+                }
+            }
+        }
+
+        let prefiltered = self.options.thumbnail_prefilter.and_then(|confidence| {
+            let thumbnail = thumbnail.unwrap_or_else(|| thumbnail_within(&result.path, &self.options))?;
+            self.prefilter_thumbnail(&thumbnail, confidence, &settings)
+        });
+        let analysis = match prefiltered {
+            Some(analysis) => {
+                self.prefiltered += 1;
+                Ok(analysis)
+            }
+            None => {
+                let frames = frames.unwrap_or_else(|| load_frames(&result.path, &self.options, None));
+                if self.options.skip_screenshots
+                    && let Ok(decoded) = &frames
+                    && let Some(hint) = screenshot_hint_sized(
+                        &result.path,
+                        &decoded.frames[0].image,
+                        decoded.frames[0].original,
+                        decoded.camera,
+                    )
+                {
+                    self.report_skip(&result.path, SkipReason::Screenshot, Some(&hint));
+                    return None;
+                }
+                // Only an image that decoded goes on to inference and waits its turn
+                frames.and_then(|decoded| {
+                    self.throttle();
+                    let analysis = self.detect(&result.path, &decoded.frames, &settings);
+                    self.last_inference = Some(Instant::now());
+                    analysis.map(|analysis| Analysis {
+                        color_profile: decoded.color_profile,
+                        ..analysis
+                    })
+                })
+            }
+        };
+
+        match analysis {
+            Ok(analysis) => {
+                if self.options.dedupe
+                    && let Some(hash) = &result.sha256
+                {
+                    self.seen_hashes.insert(
+                        hash.clone(),
+                        CachedResult {
+                            path: result.path.clone(),
+                            analysis: analysis.clone(),
+                            settings: Arc::clone(&settings),
+                        },
+                    );
+                }
+                analysis.apply_to(&mut result);
+            }
+            Err(e) => result.error = Some(e),
+        }
+
+        Some(result)
+    }
+
+    /// The empty analysis standing in for the full image's when its EXIF
+    /// thumbnail shows nothing above `confidence` ([`ScanOptions::thumbnail_prefilter`]).
+    /// `None` means the full image has to be analyzed: no thumbnail, a
+    /// possible hit, or a failed inference.
+    fn prefilter_thumbnail(
+        &mut self,
+        thumbnail: &Thumbnail,
+        confidence: f32,
+        settings: &DirSettings,
+    ) -> Option<Analysis> {
+        let mut config = settings.apply(self.detector.config()).into_owned();
+        config.confidence = confidence;
+        // Size-adaptive thresholds would rise for the tiny thumbnail
+        config.adaptive_confidence = false;
+
+        let Thumbnail {
+            image: thumbnail,
+            original,
+            color_profile,
+        } = thumbnail;
+        self.throttle();
+        let detections = self
+            .detector
+            .detect_input_with(&ModelInput::new(thumbnail, INPUT_SIZE, &config), &config)
+            .and_then(|upright| {
+                if self.options.try_rotations {
+                    detect_rotations_from(&self.detector, thumbnail, thumbnail.dimensions(), upright, &config, None)
+                        .map(|(detections, _)| detections)
+                } else {
+                    Ok(upright)
+                }
+            });
+        self.last_inference = Some(Instant::now());
+        let detections = detections.ok()?;
+
+        detections.is_empty().then(|| Analysis {
+            dimensions: *original,
+            color_profile: color_profile.clone(),
+            ..Analysis::default()
+        })
+    }
+
+    /// Whether a `.catfinder.toml` changes the settings of the image at
+    /// `path` (or cannot be read). Such images never count as known: the
+    /// cache is keyed on the scan-wide settings only.
+    fn has_dir_overrides(&mut self, path: &Path) -> bool {
+        self.options.dir_configs && !self.dir_configs.for_file(path).is_ok_and(|settings| settings.is_empty())
+    }
+
+    /// Sleep out the rest of [`ScanOptions::min_interval`] since the previous inference
+    fn throttle(&self) {
+        if let (Some(interval), Some(last)) = (self.options.min_interval, self.last_inference) {
+            thread::sleep(interval.saturating_sub(last.elapsed()));
+        }
+    }
+
+    fn report_skip(&mut self, path: &Path, reason: SkipReason, detail: Option<&dyn fmt::Display>) {
+        *self.skip_counts.entry(reason).or_default() += 1;
+
+        if !self.options.explain_skips {
+            return;
+        }
+
+        match detail {
+            Some(detail) => diag!("Skipped: {} ({reason}: {detail})", path.display()),
+            None => diag!("Skipped: {} ({reason})", path.display()),
+        }
+    }
+
+    fn detect(&self, path: &Path, frames: &[Frame], settings: &DirSettings) -> Result<Analysis> {
+        let config = settings.apply(self.detector.config());
+        let mask = settings.mask.as_deref().or(self.options.mask.as_deref());
+
+        if let Some(aggregation) = self.options.frame_agg
+            && is_animated_format(path)
+        {
+            let results = frames
+                .iter()
+                .map(|frame| self.detect_frame(path, frame, &config, mask))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(Analysis {
+                dimensions: Some(frames[0].original),
+                ..aggregate_frames(results, aggregation, config.confidence)
+            });
+        }
+
+        // Still images always decode to exactly one frame
+        self.detect_frame(path, &frames[0], &config, mask)
+    }
+
+    fn detect_frame(
+        &self,
+        path: &Path,
+        frame: &Frame,
+        config: &DetectorConfig,
+        mask: Option<&IgnoreMask>,
+    ) -> Result<Analysis> {
+        // Boxes come out at the original size even when the frame was decoded smaller
+        let (width, height) = frame.original;
+
+        if let Some(mask) = mask
+            && mask.aspect_mismatch(width, height)
+        {
+            let (mask_width, mask_height) = mask.dimensions();
+            diag!(
+                "Warning: mask is {mask_width}x{mask_height} but {} is {width}x{height}; the mask will be stretched",
+                path.display()
+            );
+        }
+        let apply_mask = |detections: &mut Vec<Detection>| {
+            if let Some(mask) = mask {
+                mask.retain_unmasked(detections, width, height);
+            }
+        };
+
+        if !self.options.scales.is_empty() {
+            let mut merged = Vec::new();
+            let mut scales = Vec::with_capacity(self.options.scales.len());
+
+            for (index, &input_size) in self.options.scales.iter().enumerate() {
+                let detections = match frame.inputs.get(index) {
+                    Some(input) => self.detector.detect_input_with(input, config)?,
+                    None => self.detector.detect_input_with(&frame.input(input_size, config), config)?,
+                };
+                let mut detections = self.with_flipped(detections, frame, input_size, config)?;
+                apply_mask(&mut detections);
+                scales.push(ScaleResult {
+                    input_size,
+                    best_confidence: detections.iter().map(|d| d.confidence).reduce(f32::max),
+                });
+                merged.extend(detections);
+            }
+
+            return Ok(Analysis {
+                detections: non_max_suppression(merged, config.iou_threshold),
+                rotation: 0,
+                scales,
+                dimensions: Some(frame.original),
+            });
+        }
+
+        let upright = match frame.inputs.first() {
+            Some(input) => self.detector.detect_input_with(input, config)?,
+            None => self.detector.detect_input_with(&frame.input(INPUT_SIZE, config), config)?,
+        };
+        let mut upright = self.with_flipped(upright, frame, INPUT_SIZE, config)?;
+        // Masked before deciding on rotations, which are only tried when upright finds nothing
+        let (detections, rotation) = if self.options.try_rotations {
+            detect_rotations_from(&self.detector, &frame.image, frame.original, upright, config, mask)?
+        } else {
+            apply_mask(&mut upright);
+            (upright, 0)
+        };
+
+        Ok(Analysis {
+            detections,
+            rotation,
+            scales: Vec::new(),
+            dimensions: Some(frame.original),
+        })
+    }
+}
+
+impl Scan<'_> {
+    /// `detections` of `frame` at `input_size` merged with those of its
+    /// mirror image under [`ScanOptions::tta`], otherwise unchanged
+    fn with_flipped(
+        &self,
+        mut detections: Vec<Detection>,
+        frame: &Frame,
+        input_size: u32,
+        config: &DetectorConfig,
+    ) -> Result<Vec<Detection>> {
+        if !self.options.tta {
+            return Ok(detections);
+        }
+
+        let mut input = ModelInput::new(&frame.image.fliph(), input_size, config);
+        (input.orig_width, input.orig_height) = frame.original;
+        let (width, _) = frame.original;
+        let flipped = self.detector.detect_input_with(&input, config)?;
+        detections.extend(flipped.into_iter().map(|d| Detection { bbox: d.bbox.unflip(width), ..d }));
+
+        Ok(non_max_suppression(detections, config.iou_threshold))
+    }
+}
+
+/// Decode the frames [`Scan::detect`] analyzes. Given the detector's config,
+/// each frame's model inputs are prepared as well.
+fn load_frames(path: &Path, options: &ScanOptions, config: Option<&DetectorConfig>) -> Result<Decoded> {
+    let mut decoded = {
+        let _span = span("decode", Some(path));
+        decode_within(path, options)?
+    };
+
+    if let Some(config) = config {
+        for frame in &mut decoded.frames {
+            frame.inputs = input_sizes(options).iter().map(|&size| frame.input(size, config)).collect();
+        }
+    }
+
+    Ok(decoded)
+}
+
+/// Whether the scan hashes each file, for [`ScanOptions::dedupe`] or
+/// [`ScanOptions::skip_hashes`]
+fn needs_hash(options: &ScanOptions) -> bool {
+    options.dedupe || options.skip_hashes.is_some()
+}
+
+/// Hash and decode one image on the preprocessing pool. The hash comes
+/// first, so files that won't be analyzed are not decoded: those listed in
+/// [`ScanOptions::skip_hashes`], and under [`ScanOptions::dedupe`] copies of a
+/// file already decoded (in `queued`). Jobs finish out of order, so now and
+/// then the earlier of two copies is the one left undecoded; the scan then
+/// decodes it itself. Under [`ScanOptions::thumbnail_prefilter`] only the
+/// EXIF thumbnail is decoded when there is one, since the full image is
+/// often not needed.
+fn prepare(path: &Path, options: &ScanOptions, config: &DetectorConfig, queued: &Mutex<HashSet<String>>) -> Prepared {
+    let sha256 = needs_hash(options).then(|| hash_within(path, options));
+
+    let skip_decode = match &sha256 {
+        None => false,
+        // Reported by the scan without decoding
+        Some(Err(_)) => true,
+        Some(Ok(hash)) => {
+            options.skip_hashes.as_ref().is_some_and(|hashes| hashes.contains(hash))
+                || (options.dedupe && !queued.lock().unwrap_or_else(|e| e.into_inner()).insert(hash.clone()))
+        }
+    };
+
+    let thumbnail = (!skip_decode && options.thumbnail_prefilter.is_some()).then(|| thumbnail_within(path, options));
+    let decode_full = !skip_decode && !thumbnail.as_ref().is_some_and(Option::is_some);
+
+    Prepared {
+        frames: decode_full.then(|| load_frames(path, options, Some(config))),
+        thumbnail,
+        sha256,
+    }
+}
+
+fn input_sizes(options: &ScanOptions) -> Vec<u32> {
+    if options.scales.is_empty() { vec![INPUT_SIZE] } else { options.scales.clone() }
+}
+
+/// The first frame of the image at `path` as a scan under `options` decodes
+/// it for the model (e.g. at a reduced JPEG scale), and the image's full size
+pub fn decode_for_scan(path: &Path, options: &ScanOptions) -> Result<(DynamicImage, (u32, u32))> {
+    let frame = decode_within(path, options)?.frames.swap_remove(0);
+    Ok((frame.image, frame.original))
+}
+
+/// Decode the image at `path` and read the EXIF the scan needs along with it
+fn decode_file(path: &Path, options: &ScanOptions) -> Result<Decoded> {
+    let frames = decode_frames(path, options)?;
+    let camera = options.skip_screenshots && PhotoMetadata::read(path).has_camera();
+
+    Ok(Decoded {
+        frames,
+        camera,
+        color_profile: probe_color(path, options),
+    })
+}
+
+/// The ICC profile embedded in the image at `path` under
+/// [`ScanOptions::probe_color`]; an unreadable one is warned about
+fn probe_color(path: &Path, options: &ScanOptions) -> Option<ColorProfile> {
+    if !options.probe_color {
+        return None;
+    }
+
+    read_color_profile(path).unwrap_or_else(|e| {
+        diag!("Warning: could not read the color profile of {}: {e}", path.display());
+        None
+    })
+}
+
+/// Every sampled frame of an animation under [`ScanOptions::frame_agg`],
+/// otherwise the first one
+fn decode_frames(path: &Path, options: &ScanOptions) -> Result<Vec<Frame>> {
+    if options.frame_agg.is_some() && is_animated_format(path) {
+        let images = open_frames(path, options.max_pixels, MAX_ANIMATION_FRAMES)?;
+        return Ok(images.into_iter().map(Frame::new).collect());
+    }
+
+    // Never below the largest input the frame is resized to
+    let min_side = input_sizes(options).into_iter().max().unwrap_or(INPUT_SIZE);
+    if options.jpeg_dct_scaling
+        && is_jpeg(path)
+        && let Some((image, original)) = open_jpeg_scaled(path, options.max_pixels, min_side)?
+    {
+        return Ok(vec![Frame {
+            image,
+            original,
+            inputs: Vec::new(),
+        }]);
+    }
+
+    Ok(vec![Frame::new(open_image(path, options.max_pixels)?)])
+}
+
+/// [`decode_file`] bounded by [`ScanOptions::io_timeout`]
+fn decode_within(path: &Path, options: &ScanOptions) -> Result<Decoded> {
+    if options.io_timeout.is_none() {
+        return decode_file(path, options);
+    }
+
+    let (job_path, job_options) = (path.to_path_buf(), options.clone());
+    read_within(path, options, move || decode_file(&job_path, &job_options))
+}
+
+/// The file's SHA-256 for [`ScanOptions::dedupe`] and
+/// [`ScanOptions::skip_hashes`], bounded by [`ScanOptions::io_timeout`]
+fn hash_within(path: &Path, options: &ScanOptions) -> Result<String> {
+    let buffer_size = options.hash_buffer_size;
+    if options.io_timeout.is_none() {
+        return calculate_sha256_with_buffer(path, buffer_size);
+    }
+
+    let job_path = path.to_path_buf();
+    read_within(path, options, move || calculate_sha256_with_buffer(&job_path, buffer_size))
+}
+
+/// The EXIF thumbnail of the image at `path` and the full image's size,
+/// bounded by [`ScanOptions::io_timeout`]. `None` when there is no thumbnail
+/// or the read fails; the full decode then reports any error.
+fn thumbnail_within(path: &Path, options: &ScanOptions) -> Option<Thumbnail> {
+    let (job_path, job_options) = (path.to_path_buf(), options.clone());
+    let read = move || {
+        Ok(exif_thumbnail(&job_path).map(|image| Thumbnail {
+            image,
+            original: image::image_dimensions(&job_path).ok(),
+            color_profile: probe_color(&job_path, &job_options),
+        }))
+    };
+
+    read_within(path, options, read).ok().flatten()
+}
+
+/// Most image reader threads alive at once. Past this many, reads still
+/// stalled after their timeout hold every slot, and further files fail
+/// straight away instead of piling up more blocked threads.
+const MAX_READER_THREADS: usize = 64;
+
+/// Image reader threads alive in this process, stalled ones included
+static READER_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Run `read` on its own thread and wait at most [`ScanOptions::io_timeout`]
+/// for it. A read stalled on e.g. a network share cannot be cancelled, so on
+/// timeout that thread is abandoned and the scan moves on.
+fn read_within<T: Send + 'static>(
+    path: &Path,
+    options: &ScanOptions,
+    read: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let Some(timeout) = options.io_timeout else {
+        return read();
+    };
+
+    if READER_THREADS.fetch_add(1, Ordering::SeqCst) >= MAX_READER_THREADS {
+        READER_THREADS.fetch_sub(1, Ordering::SeqCst);
+        return Err(DetectError::Io {
+            context: "skipped while too many earlier reads are stalled",
+            path: path.to_path_buf(),
+            source: io::Error::other(format!("{MAX_READER_THREADS} image reader threads are blocked")),
+        });
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let spawned = thread::Builder::new().name("image-reader".to_string()).spawn(move || {
+        // Released however the read ends, a panic included
+        let _slot = ReaderSlot;
+        // Nobody is waiting any more after a timeout
+        let _ = sender.send(read());
+    });
+    if let Err(e) = spawned {
+        READER_THREADS.fetch_sub(1, Ordering::SeqCst);
+        return Err(DetectError::io("failed to start image reader thread", path)(e));
+    }
+
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(DetectError::Timeout {
+            path: path.to_path_buf(),
+            timeout,
+        }),
+        Err(RecvTimeoutError::Disconnected) => Err(DetectError::Panicked {
+            path: path.to_path_buf(),
+            stage: "image reader",
+        }),
+    }
+}
+
+/// One of the [`MAX_READER_THREADS`], held by a running reader thread
+struct ReaderSlot;
+
+impl Drop for ReaderSlot {
+    fn drop(&mut self) {
+        READER_THREADS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Combine per-frame results, returning the best frame's detections when the
+/// animation as a whole is flagged
+fn aggregate_frames(frames: Vec<Analysis>, aggregation: FrameAggregation, threshold: f32) -> Analysis {
+    let frame_count = frames.len();
+    let hits = frames.iter().filter(|frame| !frame.detections.is_empty()).count();
+    let mean = frames.iter().map(Analysis::top_confidence).sum::<f32>() / frame_count.max(1) as f32;
+
+    let flagged = match aggregation {
+        FrameAggregation::Any => hits > 0,
+        FrameAggregation::Majority => hits * 2 > frame_count,
+        FrameAggregation::Mean => hits > 0 && mean >= threshold,
+    };
+
+    if !flagged {
+        return Analysis::default();
+    }
+
+    frames
+        .into_iter()
+        .max_by(|a, b| a.top_confidence().total_cmp(&b.top_confidence()))
+        .unwrap_or_default()
+}
+
+impl Scan<'_> {
+    /// Advance the walk to the next file that should be analyzed
+    fn next_image(&mut self) -> Option<PathBuf> {
+        let _span = span("walk", None);
+        while let Some(entry) = self.entries.next() {
+            self.encountered += 1;
+            let path = match entry {
+                Ok(path) => path,
+                Err(DetectError::Io { path, source, .. }) => {
+                    self.report_skip(&path, SkipReason::Unreadable, Some(&source));
+                    continue;
+                }
+                Err(DetectError::NotAFile { path }) => {
+                    self.report_skip(&path, SkipReason::NotAFile, None);
+                    continue;
+                }
+                Err(e) => {
+                    self.report_skip(Path::new(""), SkipReason::Unreadable, Some(&e));
+                    continue;
+                }
+            };
+            let path = path.as_path();
+
+            if !is_image_file(path) {
+                self.report_skip(path, SkipReason::NotAnImage, None);
+                continue;
+            }
+            if let Some(known) = &self.options.known_files
+                && let Ok(canonical) = fs::canonicalize(path)
+                && known.get(&canonical).is_some_and(|&mtime| modified_nanos(path) == Some(mtime))
+                && !self.has_dir_overrides(path)
+            {
+                self.report_skip(path, SkipReason::Known, None);
+                continue;
+            }
+
+            return Some(path.to_path_buf());
+        }
+
+        None
+    }
+}
+
+impl Iterator for Scan<'_> {
+    type Item = ScanResult;
+
+    fn next(&mut self) -> Option<ScanResult> {
+        loop {
+            if self.inference_limit_hit {
+                return None;
+            }
+
+            let (path, prepared) = self.next_prepared()?;
+            if self.options.max_inferences.is_some_and(|max| self.inferences() >= max) {
+                // There was more to analyze
+                self.inference_limit_hit = true;
+                return None;
+            }
+            if let Some(result) = self.analyze(path, prepared) {
+                return Some(result);
+            }
+        }
+    }
+}
+
+impl Scan<'_> {
+    /// The next image to analyze, with its hash and frames when they were
+    /// prepared on [`ScanOptions::preprocess_pool`]
+    fn next_prepared(&mut self) -> Option<(PathBuf, Option<Prepared>)> {
+        let Some(pool) = self.options.preprocess_pool.clone() else {
+            return Some((self.next_image()?, None));
+        };
+
+        // Keep a couple of images per preprocessing thread ahead of inference
+        while self.pending.len() < pool.current_num_threads() * 2 {
+            let Some(path) = self.next_image() else {
+                break;
+            };
+
+            let (sender, receiver) = mpsc::channel();
+            let job_path = path.clone();
+            let options = self.options.clone();
+            let config = self.detector.config().clone();
+            let queued = Arc::clone(&self.queued_hashes);
+            pool.spawn(move || {
+                // A decoder panic fails this image instead of taking down the pool
+                let prepared = panic::catch_unwind(AssertUnwindSafe(|| prepare(&job_path, &options, &config, &queued)))
+                    .unwrap_or_else(|_| preprocessing_panicked(&job_path));
+                // The scan may have been dropped; nobody is waiting then
+                let _ = sender.send(prepared);
+            });
+
+            self.pending.push_back((path, receiver));
+        }
+
+        let (path, receiver) = self.pending.pop_front()?;
+        let prepared = receiver.recv().unwrap_or_else(|_| preprocessing_panicked(&path));
+        Some((path, Some(prepared)))
+    }
+}
+
+fn preprocessing_panicked(path: &Path) -> Prepared {
+    Prepared {
+        sha256: None,
+        // Reported as is rather than settled by the thumbnail
+        thumbnail: Some(None),
+        frames: Some(Err(DetectError::Panicked {
+            path: path.to_path_buf(),
+            stage: "preprocessing",
+        })),
+    }
+}
+
+pub fn get_image_timestamp(path: &Path) -> Option<(DateTime<Local>, char)> {
+    // Get file modification time
+    fs::metadata(path)
+        .ok()
+        .and_then(|metadata| metadata.modified().ok())
+        .map(|modified| (DateTime::from(modified), 'F'))
+}
+
+/// File modification time in nanoseconds since the Unix epoch
+pub fn modified_nanos(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    DateTime::<Local>::from(modified).timestamp_nanos_opt()
+}
+
+/// Number of image files a scan of `source` would visit, counted without
+/// reading any file
+pub fn count_image_files(source: &dyn ImageSource) -> usize {
+    source
+        .images()
+        .filter_map(Result::ok)
+        .filter(|path| is_image_file(path))
+        .count()
+}
+
+pub fn is_image_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        let ext = ext.to_string_lossy().to_lowercase();
+        matches!(
+            ext.as_str(),
+            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" | "tiff" | "tif"
+        )
+    })
+}
@@ -0,0 +1,126 @@
+use crate::metadata::PhotoMetadata;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// Screen sizes in pixels (landscape; portrait matches too) that camera
+/// photos practically never have exactly
+const SCREEN_RESOLUTIONS: &[(u32, u32)] = &[
+    // Desktops and laptops
+    (1280, 720),
+    (1280, 800),
+    (1366, 768),
+    (1440, 900),
+    (1536, 864),
+    (1600, 900),
+    (1680, 1050),
+    (1920, 1080),
+    (1920, 1200),
+    (2560, 1440),
+    (2560, 1600),
+    (2880, 1800),
+    (3024, 1964),
+    (3456, 2234),
+    (3840, 2160),
+    // Phones
+    (1334, 750),
+    (1792, 828),
+    (2208, 1242),
+    (2340, 1080),
+    (2400, 1080),
+    (2436, 1125),
+    (2532, 1170),
+    (2556, 1179),
+    (2688, 1242),
+    (2778, 1284),
+    (2796, 1290),
+    (3088, 1440),
+    (3200, 1440),
+];
+
+/// Side of the thumbnail the color histogram is taken from
+const SAMPLE_SIZE: u32 = 256;
+
+/// Number of most common colors checked by the flat-color test
+const DOMINANT_COLORS: usize = 8;
+
+/// Share of pixels the dominant colors must cover for flat UI colors
+const FLAT_COLOR_SHARE: f32 = 0.7;
+
+/// Which heuristic marked an image as a screenshot or meme
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScreenshotHint {
+    /// Exactly the size of a common screen
+    ScreenResolution,
+    /// A PNG without camera EXIF tags
+    UntaggedPng,
+    /// Mostly a handful of flat colors, as in app UIs and meme captions
+    FlatColors,
+}
+
+impl fmt::Display for ScreenshotHint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ScreenshotHint::ScreenResolution => "screen-resolution",
+            ScreenshotHint::UntaggedPng => "png-without-camera-exif",
+            ScreenshotHint::FlatColors => "flat-colors",
+        })
+    }
+}
+
+/// Guess whether `image` (decoded from `path`) is a screenshot or meme
+/// rather than a photo.
+///
+/// An image whose EXIF names a camera (`Make` or `Model`) is always a photo.
+/// Otherwise the first matching test wins: exact screen resolution, PNG
+/// format, then at least 70% of pixels in the 8 most common colors (at 4 bits
+/// per channel, sampled from a 256x256 thumbnail).
+pub fn screenshot_hint(path: &Path, image: &DynamicImage) -> Option<ScreenshotHint> {
+    screenshot_hint_sized(path, image, image.dimensions(), PhotoMetadata::read(path).has_camera())
+}
+
+/// [`screenshot_hint`] for an image decoded below its `original` size (see
+/// [`crate::open_jpeg_scaled`]), whose EXIF was already read for `camera`
+pub(crate) fn screenshot_hint_sized(
+    path: &Path,
+    image: &DynamicImage,
+    original: (u32, u32),
+    camera: bool,
+) -> Option<ScreenshotHint> {
+    if camera {
+        return None;
+    }
+
+    let (width, height) = original;
+    if SCREEN_RESOLUTIONS
+        .iter()
+        .any(|&(w, h)| (width, height) == (w, h) || (width, height) == (h, w))
+    {
+        return Some(ScreenshotHint::ScreenResolution);
+    }
+
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+        return Some(ScreenshotHint::UntaggedPng);
+    }
+
+    (flat_color_share(image) >= FLAT_COLOR_SHARE).then_some(ScreenshotHint::FlatColors)
+}
+
+/// Share of pixels covered by the [`DOMINANT_COLORS`] most common colors
+fn flat_color_share(image: &DynamicImage) -> f32 {
+    // Nearest keeps exact colors; smoothing would invent blends at every UI edge
+    let sample = image.resize(SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Nearest).to_rgb8();
+
+    let mut counts: HashMap<[u8; 3], usize> = HashMap::new();
+    for pixel in sample.pixels() {
+        *counts.entry(pixel.0.map(|channel| channel >> 4)).or_default() += 1;
+    }
+
+    let mut counts: Vec<usize> = counts.into_values().collect();
+    counts.sort_unstable_by(|a, b| b.cmp(a));
+    let dominant: usize = counts.iter().take(DOMINANT_COLORS).sum();
+
+    dominant as f32 / sample.pixels().len().max(1) as f32
+}
@@ -0,0 +1,57 @@
+use crate::error::{DetectError, Result};
+use crate::record::ImageRecord;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File format for per-image sidecars
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SidecarFormat {
+    /// `<image>.json` holding the image's [`ImageRecord`]
+    Json,
+    /// `<image>.txt` with one `class_id confidence x1 y1 x2 y2` line per detection
+    Txt,
+}
+
+impl SidecarFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            SidecarFormat::Json => "json",
+            SidecarFormat::Txt => "txt",
+        }
+    }
+}
+
+/// Sidecar path for an image: the full file name plus the format's extension
+pub fn sidecar_path(image: &Path, format: SidecarFormat) -> PathBuf {
+    let mut name = image.as_os_str().to_owned();
+    name.push(".");
+    name.push(format.extension());
+    PathBuf::from(name)
+}
+
+/// Write the detections in `record` next to `image`, the file it describes.
+/// The path is taken as given rather than from the record, whose display
+/// string loses file names that are not valid UTF-8.
+pub fn write_sidecar(image: &Path, record: &ImageRecord, format: SidecarFormat) -> Result<PathBuf> {
+    let path = sidecar_path(image, format);
+
+    let contents = match format {
+        SidecarFormat::Json => {
+            let mut json = serde_json::to_string_pretty(record).expect("image records always serialize");
+            json.push('\n');
+            json
+        }
+        SidecarFormat::Txt => record
+            .detections
+            .iter()
+            .map(|d| {
+                let [x1, y1, x2, y2] = d.bbox;
+                format!("{} {:.4} {x1:.1} {y1:.1} {x2:.1} {y2:.1}\n", d.class_id, d.confidence)
+            })
+            .collect(),
+    };
+
+    fs::write(&path, contents).map_err(DetectError::io("failed to write sidecar", &path))?;
+
+    Ok(path)
+}
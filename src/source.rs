@@ -0,0 +1,184 @@
+use crate::error::{DetectError, Result};
+use crate::scan::{ScanOptions, SortOrder};
+use sha2::{Digest, Sha256};
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+use walkdir::{DirEntry, WalkDir};
+
+/// Supplies the files a [`crate::Scan`] considers.
+///
+/// [`WalkSource`] walks directories on disk; [`ListSource`] hands out a fixed
+/// list, e.g. to exercise the scan's filtering without building a tree.
+pub trait ImageSource {
+    /// Candidate files in visiting order. Directories are never listed; the
+    /// scan itself passes over entries that are not image files. An entry
+    /// that could not be read is an error, reported by the scan as
+    /// [`crate::SkipReason::Unreadable`], and one that is not a regular file
+    /// is [`DetectError::NotAFile`], reported as
+    /// [`crate::SkipReason::NotAFile`].
+    fn images(&self) -> Box<dyn Iterator<Item = Result<PathBuf>>>;
+
+    /// Directories the files are listed from. Per-directory settings are
+    /// not looked up above them (see [`crate::DirConfigs`]); none by default.
+    fn roots(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// One of `count` disjoint parts of a library, for splitting a scan across
+/// machines (`index` counts from 0).
+///
+/// A file's shard depends only on its path relative to the scan root, with
+/// `/` between components, so machines that mount the library in different
+/// places (or on different platforms) agree on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    /// Whether the file at `relative` (below the scan root) belongs to this shard
+    pub fn contains(&self, relative: &Path) -> bool {
+        let key = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let digest = Sha256::digest(key.as_bytes());
+        let hash = u64::from_be_bytes(digest[..8].try_into().expect("SHA-256 is 32 bytes"));
+        hash % self.count.max(1) as u64 == self.index as u64
+    }
+}
+
+/// Whether `relative` (below a scan root) is a hidden file or lies in a
+/// hidden directory, i.e. has a component whose name starts with `.`
+pub fn is_hidden(relative: &Path) -> bool {
+    relative.components().any(|component| match component {
+        Component::Normal(name) => is_hidden_name(name),
+        _ => false,
+    })
+}
+
+fn is_hidden_name(name: &OsStr) -> bool {
+    name.as_encoded_bytes().first() == Some(&b'.')
+}
+
+/// Every file under one or more roots, walked with the traversal settings of
+/// [`ScanOptions`] (`follow_links`, `min_depth`, `sort`, `skip_hidden`) and
+/// limited to its `shard`. A root that is a file yields just that file; its
+/// shard is decided by its file name. Roots are never skipped as hidden,
+/// even when their own name starts with `.`.
+#[derive(Clone, Debug)]
+pub struct WalkSource {
+    roots: Vec<PathBuf>,
+    follow_links: bool,
+    min_depth: usize,
+    sort: Option<SortOrder>,
+    shard: Option<Shard>,
+    skip_hidden: bool,
+}
+
+impl WalkSource {
+    pub fn new(roots: Vec<PathBuf>, options: &ScanOptions) -> Self {
+        Self {
+            roots,
+            follow_links: options.follow_links,
+            min_depth: options.min_depth,
+            sort: options.sort,
+            shard: options.shard,
+            skip_hidden: options.skip_hidden,
+        }
+    }
+
+    fn walk(&self, root: &Path) -> WalkDir {
+        let walk = WalkDir::new(root)
+            .follow_links(self.follow_links)
+            .min_depth(self.min_depth);
+
+        match self.sort {
+            None => walk,
+            Some(SortOrder::Name) => walk.sort_by_file_name(),
+            // Equal timestamps or sizes fall back to the name, so the order is fixed
+            Some(SortOrder::Mtime) => walk.sort_by(|a, b| {
+                let modified = |entry: &DirEntry| {
+                    entry.metadata().ok().and_then(|metadata| metadata.modified().ok())
+                };
+                modified(a).cmp(&modified(b)).then_with(|| a.file_name().cmp(b.file_name()))
+            }),
+            Some(SortOrder::Size) => walk.sort_by(|a, b| {
+                let size = |entry: &DirEntry| entry.metadata().map(|metadata| metadata.len()).ok();
+                size(a).cmp(&size(b)).then_with(|| a.file_name().cmp(b.file_name()))
+            }),
+        }
+    }
+}
+
+impl ImageSource for WalkSource {
+    fn images(&self) -> Box<dyn Iterator<Item = Result<PathBuf>>> {
+        let (shard, skip_hidden) = (self.shard, self.skip_hidden);
+        let walks: Vec<(PathBuf, WalkDir)> = self.roots.iter().map(|root| (root.clone(), self.walk(root))).collect();
+
+        Box::new(walks.into_iter().flat_map(move |(root, walk)| {
+            // Pruning hidden directories here spares walking their contents at all
+            let walk = walk
+                .into_iter()
+                .filter_entry(move |entry| !(skip_hidden && entry.depth() > 0 && is_hidden_name(entry.file_name())));
+            walk.filter_map(move |entry| match entry {
+                Ok(entry) if entry.file_type().is_dir() => None,
+                // A symlink to a file counts even when links are not followed
+                Ok(entry) if !entry.file_type().is_file() && !entry.path().is_file() => {
+                    Some(Err(DetectError::NotAFile { path: entry.into_path() }))
+                }
+                Ok(entry) => {
+                    if let Some(shard) = shard {
+                        let relative = match entry.path().strip_prefix(&root) {
+                            Ok(relative) if !relative.as_os_str().is_empty() => relative,
+                            _ => Path::new(entry.file_name()),
+                        };
+                        if !shard.contains(relative) {
+                            return None;
+                        }
+                    }
+                    Some(Ok(entry.into_path()))
+                }
+                Err(e) => {
+                    let path = e.path().map(Path::to_path_buf).unwrap_or_default();
+                    Some(Err(DetectError::Io {
+                        context: "failed to read directory entry",
+                        path,
+                        source: e.into(),
+                    }))
+                }
+            })
+        }))
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        // A root that is a file has no directory below it
+        self.roots.iter().filter(|root| root.is_dir()).cloned().collect()
+    }
+}
+
+/// A fixed list of files, handed out as given without touching the disk.
+/// Every entry counts as a regular file.
+#[derive(Clone, Debug, Default)]
+pub struct ListSource {
+    paths: Vec<PathBuf>,
+}
+
+impl ListSource {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self { paths }
+    }
+}
+
+impl ImageSource for ListSource {
+    fn images(&self) -> Box<dyn Iterator<Item = Result<PathBuf>>> {
+        Box::new(self.paths.clone().into_iter().map(Ok))
+    }
+}
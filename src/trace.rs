@@ -0,0 +1,153 @@
+use crate::error::{DetectError, Result};
+use serde::Serialize;
+use std::cell::Cell;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Instant;
+
+static TRACE: OnceLock<Trace> = OnceLock::new();
+
+/// Trace thread IDs handed out so far
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    // This thread's ID in the trace, 0 until its first span
+    static THREAD_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+struct Trace {
+    started: Instant,
+    events: Mutex<Vec<TraceEvent>>,
+}
+
+/// One event in the Chrome trace event format: a complete span (`X`) or
+/// thread name metadata (`M`)
+#[derive(Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    ph: &'static str,
+    /// Start in microseconds since tracing was enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ts: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<f64>,
+    pid: u32,
+    tid: u64,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    args: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceFile<'a> {
+    trace_events: &'a [TraceEvent],
+    display_time_unit: &'static str,
+}
+
+/// Start recording timing spans (walk, decode, preprocess, infer,
+/// postprocess) from every thread, for [`write_trace`]. Only the first call
+/// takes effect; returns whether this one did.
+pub fn enable_tracing() -> bool {
+    TRACE
+        .set(Trace {
+            started: Instant::now(),
+            events: Mutex::new(Vec::new()),
+        })
+        .is_ok()
+}
+
+/// A stage of work being timed; recorded when dropped
+pub(crate) struct Span {
+    name: &'static str,
+    path: Option<String>,
+    started: Instant,
+}
+
+/// Time the current scope as the stage `name`, optionally of the image at
+/// `path`. `None` (and no cost beyond the check) when tracing is off.
+pub(crate) fn span(name: &'static str, path: Option<&Path>) -> Option<Span> {
+    TRACE.get()?;
+    Some(Span {
+        name,
+        path: path.map(|path| path.display().to_string()),
+        started: Instant::now(),
+    })
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let Some(trace) = TRACE.get() else {
+            return;
+        };
+
+        let ts = self.started.duration_since(trace.started).as_secs_f64() * 1e6;
+        let dur = self.started.elapsed().as_secs_f64() * 1e6;
+        let mut args = serde_json::Map::new();
+        if let Some(path) = self.path.take() {
+            args.insert("path".to_string(), path.into());
+        }
+
+        let mut events = trace.events.lock().unwrap_or_else(|e| e.into_inner());
+        let tid = thread_id(&mut events);
+        events.push(TraceEvent {
+            name: self.name,
+            ph: "X",
+            ts: Some(ts),
+            dur: Some(dur),
+            pid: 1,
+            tid,
+            args,
+        });
+    }
+}
+
+/// The current thread's trace ID, naming the thread in `events` the first time
+fn thread_id(events: &mut Vec<TraceEvent>) -> u64 {
+    THREAD_ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+
+            let current = thread::current();
+            let mut args = serde_json::Map::new();
+            args.insert("name".to_string(), current.name().unwrap_or("worker").into());
+            events.push(TraceEvent {
+                name: "thread_name",
+                ph: "M",
+                ts: None,
+                dur: None,
+                pid: 1,
+                tid: id.get(),
+                args,
+            });
+        }
+        id.get()
+    })
+}
+
+/// Write the spans recorded since [`enable_tracing`] to `path` as Chrome
+/// trace JSON, for chrome://tracing or Perfetto. Does nothing when tracing
+/// is off.
+pub fn write_trace(path: &Path) -> Result<()> {
+    let Some(trace) = TRACE.get() else {
+        return Ok(());
+    };
+
+    let events = trace.events.lock().unwrap_or_else(|e| e.into_inner());
+    let file = TraceFile {
+        trace_events: &events,
+        display_time_unit: "ms",
+    };
+    let json = serde_json::to_string(&file).expect("trace events serialize");
+    fs::write(path, json).map_err(DetectError::io("failed to write trace", path))
+}
+
+/// Number of spans recorded so far
+pub fn trace_len() -> usize {
+    TRACE.get().map_or(0, |trace| {
+        let events = trace.events.lock().unwrap_or_else(|e| e.into_inner());
+        events.iter().filter(|event| event.ph == "X").count()
+    })
+}
@@ -0,0 +1,147 @@
+use crate::error::{DetectError, Result};
+use crate::scan::is_image_file;
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// A file seen changing that has not settled yet
+struct Pending {
+    size: Option<u64>,
+    changed: Instant,
+}
+
+/// Reports image files created or modified below a directory, once they
+/// are fully written.
+///
+/// Copies and camera uploads arrive in many writes, each raising an event.
+/// A file is only handed out after `settle` has passed without events for
+/// it and with its size unchanged over that time, so half-written images
+/// are not analyzed.
+pub struct FileWatcher {
+    root: PathBuf,
+    // Dropping the watcher stops the events
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    pending: HashMap<PathBuf, Pending>,
+    settle: Duration,
+}
+
+impl FileWatcher {
+    /// Start watching `root` and everything below it
+    pub fn new(root: &Path, settle: Duration) -> Result<Self> {
+        let watch_error = |source| DetectError::Watch {
+            path: root.to_path_buf(),
+            source,
+        };
+
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+        watcher.watch(root, RecursiveMode::Recursive).map_err(watch_error)?;
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            _watcher: watcher,
+            events,
+            pending: HashMap::new(),
+            settle,
+        })
+    }
+
+    /// Wait until at least one new or changed image file has settled and
+    /// return all that have, in path order
+    pub fn wait(&mut self) -> Result<Vec<PathBuf>> {
+        loop {
+            let event = if self.pending.is_empty() {
+                self.events.recv().map_err(Into::into)
+            } else {
+                self.events.recv_timeout(self.poll_interval())
+            };
+
+            match event {
+                Ok(Ok(event)) => self.record(event),
+                Ok(Err(source)) => {
+                    return Err(DetectError::Watch {
+                        path: self.root.clone(),
+                        source,
+                    });
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                // Only happens once the watcher itself is gone
+                Err(RecvTimeoutError::Disconnected) => return Ok(Vec::new()),
+            }
+
+            let ready = self.settled();
+            if !ready.is_empty() {
+                return Ok(ready);
+            }
+        }
+    }
+
+    fn record(&mut self, event: Event) {
+        if event.need_rescan() {
+            diag!("Warning: missed file events under {}; some new images may be left out", self.root.display());
+        }
+
+        match event.kind {
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any) => {
+                for path in event.paths {
+                    if !is_image_file(&path) {
+                        continue;
+                    }
+                    let size = fs::metadata(&path).ok().filter(|m| m.is_file()).map(|m| m.len());
+                    self.pending.insert(
+                        path,
+                        Pending {
+                            size,
+                            changed: Instant::now(),
+                        },
+                    );
+                }
+            }
+            EventKind::Remove(_) => {
+                for path in &event.paths {
+                    self.pending.remove(path);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Take the pending files that have been quiet for `settle` at a steady,
+    /// non-zero size; files still growing start another wait
+    fn settled(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        let mut ready = Vec::new();
+
+        self.pending.retain(|path, pending| {
+            if now.duration_since(pending.changed) < self.settle {
+                return true;
+            }
+
+            let size = match fs::metadata(path) {
+                Ok(metadata) if metadata.is_file() => metadata.len(),
+                // Deleted or replaced by a directory
+                _ => return false,
+            };
+            if size > 0 && pending.size == Some(size) {
+                ready.push(path.clone());
+                return false;
+            }
+
+            pending.size = Some(size);
+            pending.changed = now;
+            true
+        });
+
+        ready.sort();
+        ready
+    }
+
+    fn poll_interval(&self) -> Duration {
+        (self.settle / 4).max(Duration::from_millis(50))
+    }
+}
@@ -0,0 +1,142 @@
+use cat_finder::{
+    BoundingBox, CAT_CLASS_ID, CatDetector, Detection, DetectionRecord, DetectorConfig, ImageRecord, ListSource,
+    ModelInput, Result, Scan, ScanOptions, SkipReason, SortOrder, count_image_files,
+};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Stands in for the model without ONNX Runtime: one box over the whole
+/// input whose confidence is its mean brightness, so results still depend
+/// on every decoded pixel
+#[derive(Default)]
+struct BrightnessDetector {
+    config: DetectorConfig,
+}
+
+impl CatDetector for BrightnessDetector {
+    fn config(&self) -> &DetectorConfig {
+        &self.config
+    }
+
+    fn detect_input_with(&self, input: &ModelInput, config: &DetectorConfig) -> Result<Vec<Detection>> {
+        let pixels = input.to_rgb_image(&config.normalization).into_raw();
+        let mean = pixels.iter().map(|&value| f32::from(value)).sum::<f32>() / pixels.len() as f32 / 255.0;
+        let size = input.input_size() as f32;
+
+        Ok(vec![Detection {
+            class_id: CAT_CLASS_ID,
+            confidence: mean,
+            bbox: BoundingBox {
+                x1: 0.0,
+                y1: 0.0,
+                x2: size,
+                y2: size,
+            },
+        }])
+    }
+}
+
+fn samples() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("samples")
+}
+
+/// What `--format json` would print for a scan of `samples/`, errors included
+fn scan_output(options: ScanOptions) -> String {
+    let detector = BrightnessDetector::default();
+    let detector: &dyn CatDetector = &detector;
+
+    records(detector.scan(&samples(), options))
+}
+
+/// What `--format json` would print for `scan`, errors included
+fn records(scan: Scan<'_>) -> String {
+    let mut output = String::new();
+    for result in scan {
+        if let Some(e) = &result.error {
+            output.push_str(&format!("error {}: {e}\n", result.path.display()));
+            continue;
+        }
+
+        let record = ImageRecord {
+            path: result.path.display().to_string(),
+            timestamp: None,
+            sha256: result.sha256.clone(),
+            exif: None,
+            rotation: result.rotation,
+            detections: result.detections.iter().map(DetectionRecord::from).collect(),
+            scales: result.scales.clone(),
+            maybe: false,
+        };
+        output.push_str(&serde_json::to_string(&record).expect("records serialize"));
+        output.push('\n');
+    }
+    output
+}
+
+fn sorted() -> ScanOptions {
+    ScanOptions {
+        sort: Some(SortOrder::Name),
+        ..ScanOptions::default()
+    }
+}
+
+#[test]
+fn repeated_scans_are_identical() {
+    let first = scan_output(sorted());
+    let second = scan_output(sorted());
+
+    assert_eq!(first.lines().count(), 20, "every image in samples/:\n{first}");
+    assert_eq!(first, second);
+}
+
+#[test]
+fn preprocess_threads_keep_order_and_output() {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().expect("thread pool starts");
+    let threaded = ScanOptions {
+        preprocess_pool: Some(Arc::new(pool)),
+        dedupe: true,
+        ..sorted()
+    };
+    let serial = ScanOptions { dedupe: true, ..sorted() };
+
+    assert_eq!(scan_output(serial), scan_output(threaded));
+}
+
+#[test]
+fn list_source_matches_the_walk() {
+    let mut paths: Vec<PathBuf> = fs::read_dir(samples())
+        .expect("samples/ is readable")
+        .map(|entry| entry.expect("samples/ is readable").path())
+        .collect();
+    paths.sort();
+
+    let detector = BrightnessDetector::default();
+    let detector: &dyn CatDetector = &detector;
+    let listed = records(detector.scan_source(&ListSource::new(paths), ScanOptions::default()));
+
+    assert_eq!(listed, scan_output(sorted()));
+}
+
+#[test]
+fn list_source_is_filtered_without_touching_the_disk() {
+    let source = ListSource::new(vec![
+        PathBuf::from("nowhere/notes.txt"),
+        PathBuf::from("nowhere/cat.jpg"),
+        PathBuf::from("nowhere/README"),
+    ]);
+    assert_eq!(count_image_files(&source), 1);
+
+    let detector = BrightnessDetector::default();
+    let detector: &dyn CatDetector = &detector;
+    let mut scan = detector.scan_source(&source, ScanOptions::default());
+    let results: Vec<_> = scan.by_ref().collect();
+
+    // The one image is attempted, and fails only when it is opened
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].path, PathBuf::from("nowhere/cat.jpg"));
+    assert!(results[0].error.is_some());
+    assert_eq!(scan.encountered(), 3);
+    assert_eq!(scan.skipped(SkipReason::NotAnImage), 2);
+    assert_eq!(scan.skipped(SkipReason::NotAFile), 0);
+}